@@ -1,7 +1,10 @@
 use bevy::prelude::*;
 
-use crate::quadtree::{Quadtree, X_EXTENT, Y_EXTENT};
-const LOOK_DIST: f32 = 30f32;
+use crate::goal::BoidGoal;
+use crate::quadtree::{Quadtree, WorldBounds};
+use crate::replay::SimMode;
+pub const PERCEPTION_RADIUS: f32 = 5.0;
+const BOID_SIZE: Vec2 = Vec2::new(1.0, 2.0);
 
 #[derive(Component)]
 pub struct Boid {
@@ -9,22 +12,36 @@ pub struct Boid {
     pub speed: f32,
 }
 
+// Every boid shares the same texture, the colour comes from its Tint
+pub fn boid_sprite(texture: &Handle<Image>, transform: Transform) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            custom_size: Some(BOID_SIZE),
+            ..default()
+        },
+        texture: texture.clone(),
+        transform,
+        ..default()
+    }
+}
+
 pub struct BoidPlugin;
 impl Plugin for BoidPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, update_boid);
+        app.add_systems(Update, update_boid.run_if(in_state(SimMode::Live)));
     }
 }
 
-fn update_boid(
-    mut query: Query<(Entity, &Boid, &mut Transform)>,
+pub fn update_boid(
+    mut query: Query<(Entity, &Boid, &mut Transform, Option<&BoidGoal>)>,
     quadtree: Res<Quadtree>,
+    bounds: Res<WorldBounds>,
     time: Res<Time>,
 ) {
     let deltasec = time.delta_seconds();
     //update angle:
-    for (_, boid, mut transform) in query.iter_mut() {
-        let padding = 5.0;
+    for (_, boid, mut transform, _) in query.iter_mut() {
+        let padding = PERCEPTION_RADIUS;
 
         let mut transforms: Vec<Transform> = Vec::new();
 
@@ -55,14 +72,15 @@ fn update_boid(
     }
 
     //update pos
-    for (_, boid, mut transform) in query.iter_mut() {
-        let speed = transform.up() * boid.speed;
+    for (_, boid, mut transform, goal) in query.iter_mut() {
+        let throttle = goal.map_or(1.0, |goal| goal.throttle);
+        let speed = transform.up() * boid.speed * throttle;
         transform.translation -= speed * time.delta_seconds();
 
-        if transform.translation.x.abs() > X_EXTENT {
+        if transform.translation.x.abs() > bounds.half_extents.x {
             transform.translation.x *= -1.0;
         }
-        if transform.translation.y.abs() > Y_EXTENT {
+        if transform.translation.y.abs() > bounds.half_extents.y {
             transform.translation.y *= -1.0;
         }
     }
@@ -0,0 +1,350 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::boid::{Boid, PERCEPTION_RADIUS};
+use crate::quadtree::Quadtree;
+use crate::replay::SimMode;
+use crate::tint::{apply_tint, Tint, TintMode};
+
+// Radius of the centroid marker of a flock, per square root of its size
+const CENTROID_MARKER_SCALE: f32 = 1.0;
+
+pub struct FlockPlugin;
+impl Plugin for FlockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Flocks>()
+            .add_event::<FlockEvent>()
+            .add_systems(
+                Update,
+                (
+                    detect_flocks.run_if(in_state(SimMode::Live)),
+                    color_flocks.run_if(resource_equals(TintMode::Flock)),
+                )
+                    .chain()
+                    .before(apply_tint),
+            )
+            .add_systems(
+                Update,
+                (
+                    log_flock_events.after(detect_flocks),
+                    draw_flock_centroids.run_if(resource_equals(TintMode::Flock)),
+                ),
+            );
+    }
+}
+
+// Stable id of the flock a boid belongs to, kept across frames
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FlockId(pub u32);
+
+#[derive(Debug, Clone, Copy)]
+pub struct FlockInfo {
+    pub size: usize,
+    pub centroid: Vec2,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Flocks {
+    pub flocks: HashMap<FlockId, FlockInfo>,
+    next_id: u32,
+}
+
+impl Flocks {
+    fn new_id(&mut self) -> FlockId {
+        let id = FlockId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlockEvent {
+    // `from` no longer exists, its boids are now part of `into`
+    Merged { into: FlockId, from: FlockId },
+    // Part of `from` broke away and became `into`
+    Split { from: FlockId, into: FlockId },
+}
+
+// Disjoint set over the indices of the boids
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
+
+fn detect_flocks(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Transform, Option<&mut FlockId>), With<Boid>>,
+    quadtree: Res<Quadtree>,
+    mut flocks: ResMut<Flocks>,
+    mut events: EventWriter<FlockEvent>,
+) {
+    let boids: Vec<(Entity, Vec2, Option<FlockId>)> = query
+        .iter()
+        .map(|(entity, transform, id)| (entity, transform.translation.xy(), id.copied()))
+        .collect();
+    let index: HashMap<Entity, usize> = boids
+        .iter()
+        .enumerate()
+        .map(|(i, (entity, _, _))| (*entity, i))
+        .collect();
+
+    //connect every pair of boids that can see each other
+    let mut pairs: Vec<[usize; 2]> = Vec::new();
+    let mut candidates: Vec<Entity> = Vec::new();
+    for (i, (_, position, _)) in boids.iter().enumerate() {
+        candidates.clear();
+        let area = Rect::from_center_half_size(*position, Vec2::splat(PERCEPTION_RADIUS));
+        quadtree.query_entities(area, &mut candidates);
+
+        for candidate in candidates.iter() {
+            if let Some(&j) = index.get(candidate) {
+                if j > i && position.distance(boids[j].1) <= PERCEPTION_RADIUS {
+                    pairs.push([i, j]);
+                }
+            }
+        }
+    }
+
+    let components = group_flocks(boids.len(), &pairs);
+    let previous: Vec<Option<FlockId>> = boids.iter().map(|(_, _, id)| *id).collect();
+    let (ids, flock_events) = assign_flock_ids(&components, &previous, &mut flocks);
+    events.send_batch(flock_events);
+
+    flocks.flocks.clear();
+    for (members, id) in components.iter().zip(ids.iter()) {
+        let centroid = members.iter().map(|&i| boids[i].1).sum::<Vec2>() / members.len() as f32;
+        flocks.flocks.insert(
+            *id,
+            FlockInfo {
+                size: members.len(),
+                centroid,
+            },
+        );
+
+        for &i in members.iter() {
+            let entity = boids[i].0;
+            match query.get_mut(entity) {
+                Ok((_, _, Some(mut flock_id))) => {
+                    if *flock_id != *id {
+                        *flock_id = *id;
+                    }
+                }
+                _ => {
+                    commands.entity(entity).insert(*id);
+                }
+            }
+        }
+    }
+}
+
+// Groups of boids linked by `pairs`, directly or through other boids.
+// Biggest first, ties broken by their lowest index so the order is stable.
+fn group_flocks(count: usize, pairs: &[[usize; 2]]) -> Vec<Vec<usize>> {
+    let mut sets = UnionFind::new(count);
+    for &[i, j] in pairs {
+        sets.union(i, j);
+    }
+
+    let mut components: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..count {
+        components.entry(sets.find(i)).or_default().push(i);
+    }
+
+    let mut components: Vec<Vec<usize>> = components.into_values().collect();
+    components.sort_by_key(|members| (std::cmp::Reverse(members.len()), members[0]));
+    components
+}
+
+// Picks an id for each group from the ones its boids had last frame, and reports
+// how the old flocks ended up in the new ones
+fn assign_flock_ids(
+    components: &[Vec<usize>],
+    previous: &[Option<FlockId>],
+    flocks: &mut Flocks,
+) -> (Vec<FlockId>, Vec<FlockEvent>) {
+    // Biggest flocks pick their id first, so the largest piece of a split keeps the old id
+    let mut assigned: Vec<(FlockId, HashMap<FlockId, usize>)> = Vec::new();
+    let mut taken: HashSet<FlockId> = HashSet::new();
+    for members in components.iter() {
+        let mut counts: HashMap<FlockId, usize> = HashMap::new();
+        for &i in members.iter() {
+            if let Some(id) = previous[i] {
+                *counts.entry(id).or_default() += 1;
+            }
+        }
+
+        let mut ranked: Vec<(FlockId, usize)> = counts.iter().map(|(id, n)| (*id, *n)).collect();
+        ranked.sort_by_key(|(id, n)| (std::cmp::Reverse(*n), id.0));
+        let id = ranked
+            .iter()
+            .map(|(id, _)| *id)
+            .find(|id| !taken.contains(id))
+            .unwrap_or_else(|| flocks.new_id());
+
+        taken.insert(id);
+        assigned.push((id, counts));
+    }
+
+    let mut events = Vec::new();
+    for (id, counts) in assigned.iter() {
+        let mut olds: Vec<FlockId> = counts.keys().copied().collect();
+        olds.sort_by_key(|old| old.0);
+        for old in olds {
+            if old == *id {
+                continue;
+            }
+            if taken.contains(&old) {
+                events.push(FlockEvent::Split {
+                    from: old,
+                    into: *id,
+                });
+            } else {
+                events.push(FlockEvent::Merged {
+                    into: *id,
+                    from: old,
+                });
+            }
+        }
+    }
+
+    (assigned.into_iter().map(|(id, _)| id).collect(), events)
+}
+
+fn log_flock_events(mut events: EventReader<FlockEvent>, flocks: Res<Flocks>) {
+    for event in events.read() {
+        let size = |id| flocks.flocks.get(&id).map_or(0, |flock| flock.size);
+        match *event {
+            FlockEvent::Merged { into, from } => {
+                debug!(
+                    "flock {} merged into {} ({} boids)",
+                    from.0,
+                    into.0,
+                    size(into)
+                )
+            }
+            FlockEvent::Split { from, into } => {
+                debug!(
+                    "flock {} split off {} ({} boids)",
+                    from.0,
+                    into.0,
+                    size(into)
+                )
+            }
+        }
+    }
+}
+
+pub fn flock_color(id: FlockId) -> Color {
+    // Golden angle steps keep neighbouring ids far apart on the hue wheel
+    let hue = (id.0 as f32 * 137.508) % 360.0;
+    Color::hsl(hue, 0.8, 0.6)
+}
+
+fn color_flocks(mut query: Query<(&FlockId, &mut Tint)>, flocks: Res<Flocks>) {
+    for (id, mut tint) in query.iter_mut() {
+        // Lone boids stay white
+        let alone = flocks.flocks.get(id).map_or(0, |flock| flock.size) < 2;
+        let color = if alone {
+            Color::WHITE
+        } else {
+            flock_color(*id)
+        };
+
+        // Only flag the tint as changed when the colour actually changes
+        if tint.0 != color {
+            tint.0 = color;
+        }
+    }
+}
+
+// Marks the middle of every flock, bigger flocks get a bigger circle
+fn draw_flock_centroids(mut gizmos: Gizmos, flocks: Res<Flocks>) {
+    for (id, flock) in flocks.flocks.iter() {
+        if flock.size < 2 {
+            continue;
+        }
+        let radius = (flock.size as f32).sqrt() * CENTROID_MARKER_SCALE;
+        gizmos.circle_2d(flock.centroid, radius, flock_color(*id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boids_linked_through_others_share_a_flock() {
+        // 0-1-2 form a chain, 3-4 a pair and 5 is alone
+        let components = group_flocks(6, &[[0, 1], [1, 2], [3, 4]]);
+        assert_eq!(components, vec![vec![0, 1, 2], vec![3, 4], vec![5]]);
+    }
+
+    #[test]
+    fn new_groups_get_new_ids() {
+        let mut flocks = Flocks::default();
+        let components = group_flocks(4, &[[0, 1], [2, 3]]);
+        let (ids, events) = assign_flock_ids(&components, &[None; 4], &mut flocks);
+        assert_eq!(ids, vec![FlockId(0), FlockId(1)]);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn joining_flocks_merge_into_the_bigger_one() {
+        let mut flocks = Flocks::default();
+        let previous = [Some(FlockId(7)), Some(FlockId(3)), Some(FlockId(3))];
+        let components = group_flocks(3, &[[0, 1], [1, 2]]);
+        let (ids, events) = assign_flock_ids(&components, &previous, &mut flocks);
+        assert_eq!(ids, vec![FlockId(3)]);
+        assert_eq!(
+            events,
+            vec![FlockEvent::Merged {
+                into: FlockId(3),
+                from: FlockId(7),
+            }]
+        );
+    }
+
+    #[test]
+    fn largest_piece_of_a_split_keeps_the_id() {
+        let mut flocks = Flocks {
+            next_id: 4,
+            ..default()
+        };
+        let previous = [Some(FlockId(2)); 5];
+        let components = group_flocks(5, &[[0, 1], [3, 4], [2, 3]]);
+        let (ids, events) = assign_flock_ids(&components, &previous, &mut flocks);
+        assert_eq!(ids, vec![FlockId(2), FlockId(4)]);
+        assert_eq!(
+            events,
+            vec![FlockEvent::Split {
+                from: FlockId(2),
+                into: FlockId(4),
+            }]
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use serde::Deserialize;
+
+use crate::boid::{update_boid, Boid};
+use crate::replay::SimMode;
+
+pub struct GoalPlugin;
+impl Plugin for GoalPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<BoidPath>().add_systems(
+            Update,
+            steer_to_goal
+                .before(update_boid)
+                .run_if(in_state(SimMode::Live)),
+        );
+    }
+}
+
+// Polyline the boids walk through, in world coordinates
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct BoidPath {
+    pub waypoints: Vec<Vec2>,
+    #[serde(default)]
+    pub looping: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum GoalTarget {
+    // Go through the waypoints of a path, `next` is the one we are heading to
+    Path { path: Handle<BoidPath>, next: usize },
+    // Keep a slot next to the leader, `offset` is relative to the leader heading
+    Leader { leader: Entity, offset: Vec2 },
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct BoidGoal {
+    pub target: GoalTarget,
+    // How strong the seek term is compared to the flocking one
+    pub weight: f32,
+    // Distance at which a waypoint counts as reached and the boid starts slowing down
+    pub arrive_radius: f32,
+    // Fraction of the boid speed to use this frame, set by the arrive behaviour
+    pub throttle: f32,
+}
+
+impl BoidGoal {
+    pub fn path(path: Handle<BoidPath>, weight: f32, arrive_radius: f32) -> Self {
+        Self {
+            target: GoalTarget::Path { path, next: 0 },
+            weight,
+            arrive_radius,
+            throttle: 1.0,
+        }
+    }
+
+    pub fn leader(leader: Entity, offset: Vec2, weight: f32, arrive_radius: f32) -> Self {
+        Self {
+            target: GoalTarget::Leader { leader, offset },
+            weight,
+            arrive_radius,
+            throttle: 1.0,
+        }
+    }
+}
+
+// Boids move along their local -Y axis
+pub fn heading(transform: &Transform) -> Vec2 {
+    (transform.rotation * Vec3::NEG_Y).xy()
+}
+
+fn steer_to_goal(
+    mut query: Query<(&Boid, &mut Transform, Option<&mut BoidGoal>)>,
+    paths: Res<Assets<BoidPath>>,
+    time: Res<Time>,
+) {
+    let deltasec = time.delta_seconds();
+
+    // Leaders can be followers too, so read their positions before moving anything
+    let mut leaders: HashMap<Entity, (Vec2, Vec2)> = HashMap::new();
+    for (_, _, goal) in query.iter() {
+        if let Some(GoalTarget::Leader { leader, .. }) = goal.map(|goal| &goal.target) {
+            leaders.insert(*leader, (Vec2::ZERO, Vec2::ZERO));
+        }
+    }
+    leaders.retain(|leader, state| {
+        let Ok((_, transform, _)) = query.get(*leader) else {
+            return false;
+        };
+        *state = (transform.translation.xy(), heading(transform));
+        true
+    });
+
+    for (boid, mut transform, goal) in query.iter_mut() {
+        let Some(mut goal) = goal else {
+            continue;
+        };
+        let position = transform.translation.xy();
+        let arrive_radius = goal.arrive_radius;
+
+        // Where to go, and whether to slow down when getting there
+        let target = match &mut goal.target {
+            GoalTarget::Path { path, next } => {
+                let Some(path) = paths.get(path.id()) else {
+                    continue;
+                };
+                if path.waypoints.is_empty() {
+                    continue;
+                }
+                *next = (*next).min(path.waypoints.len() - 1);
+
+                if position.distance(path.waypoints[*next]) < arrive_radius {
+                    if *next + 1 < path.waypoints.len() {
+                        *next += 1;
+                    } else if path.looping {
+                        *next = 0;
+                    }
+                }
+                let last = !path.looping && *next == path.waypoints.len() - 1;
+                Some((path.waypoints[*next], last))
+            }
+            GoalTarget::Leader { leader, offset } => {
+                leaders
+                    .get(leader)
+                    .map(|(leader_position, leader_heading)| {
+                        // Rotate the slot so the formation turns with the leader
+                        let side = leader_heading.perp();
+                        let slot = *leader_position + side * offset.x + *leader_heading * offset.y;
+                        (slot, true)
+                    })
+            }
+        };
+
+        let Some((target, arrive)) = target else {
+            goal.throttle = 1.0;
+            continue;
+        };
+
+        let desired = target - position;
+        let distance = desired.length();
+        goal.throttle = if arrive {
+            (distance / arrive_radius).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        if distance < f32::EPSILON {
+            continue;
+        }
+
+        let angle = heading(&transform).angle_between(desired);
+        let turn = (goal.weight * boid.rotation_speed * deltasec).min(1.0);
+        transform.rotate_z(angle * turn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+    use std::time::Duration;
+
+    use super::*;
+
+    // App steering once per update, each update lasting `seconds`
+    fn app_with(seconds: f32) -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<Assets<BoidPath>>()
+            .add_systems(
+                Update,
+                (
+                    move |mut time: ResMut<Time>| time.advance_by(Duration::from_secs_f32(seconds)),
+                    steer_to_goal,
+                )
+                    .chain(),
+            );
+        app
+    }
+
+    // Turns fully towards its target every second
+    fn boid() -> Boid {
+        Boid {
+            rotation_speed: 1.0,
+            speed: 10.0,
+        }
+    }
+
+    fn spawn(app: &mut App, transform: Transform, goal: BoidGoal) -> Entity {
+        app.world.spawn((boid(), transform, goal)).id()
+    }
+
+    fn follow(app: &mut App, waypoints: Vec<Vec2>, looping: bool) -> Entity {
+        let path = app
+            .world
+            .resource_mut::<Assets<BoidPath>>()
+            .add(BoidPath { waypoints, looping });
+        spawn(app, Transform::default(), BoidGoal::path(path, 1.0, 5.0))
+    }
+
+    fn move_to(app: &mut App, boid: Entity, position: Vec2) {
+        app.world.get_mut::<Transform>(boid).unwrap().translation = position.extend(0.0);
+        app.update();
+    }
+
+    fn goal(app: &App, boid: Entity) -> (Option<usize>, f32) {
+        let goal = app.world.get::<BoidGoal>(boid).unwrap();
+        let next = match goal.target {
+            GoalTarget::Path { next, .. } => Some(next),
+            GoalTarget::Leader { .. } => None,
+        };
+        (next, goal.throttle)
+    }
+
+    // Reaching a waypoint heads to the next one, at full speed until the last one
+    #[test]
+    fn paths_advance_through_their_waypoints() {
+        let mut app = app_with(0.0);
+        let waypoints = vec![Vec2::ZERO, Vec2::new(100.0, 0.0), Vec2::new(100.0, 100.0)];
+        let boid = follow(&mut app, waypoints, false);
+
+        move_to(&mut app, boid, Vec2::new(1.0, 0.0));
+        assert_eq!(goal(&app, boid), (Some(1), 1.0));
+        // Not there yet
+        move_to(&mut app, boid, Vec2::new(50.0, 0.0));
+        assert_eq!(goal(&app, boid), (Some(1), 1.0));
+        move_to(&mut app, boid, Vec2::new(100.0, 2.0));
+        assert_eq!(goal(&app, boid).0, Some(2));
+    }
+
+    // The last waypoint is kept, unless the path loops back to the first one
+    #[test]
+    fn paths_stop_at_the_end_or_loop() {
+        let waypoints = vec![Vec2::ZERO, Vec2::new(100.0, 0.0)];
+        for (looping, expected) in [(false, (Some(1), 0.0)), (true, (Some(0), 1.0))] {
+            let mut app = app_with(0.0);
+            let boid = follow(&mut app, waypoints.clone(), looping);
+            move_to(&mut app, boid, Vec2::ZERO);
+            move_to(&mut app, boid, Vec2::new(100.0, 0.0));
+            assert_eq!(goal(&app, boid), expected, "looping {}", looping);
+            // and stays there
+            move_to(&mut app, boid, Vec2::new(100.0, 0.0));
+            assert_eq!(goal(&app, boid), expected, "looping {}", looping);
+        }
+    }
+
+    // Approaching the last waypoint slows down over the arrive radius
+    #[test]
+    fn throttle_ramps_down_near_the_end() {
+        let mut app = app_with(0.0);
+        let boid = follow(&mut app, vec![Vec2::ZERO], false);
+
+        for (x, throttle) in [(20.0, 1.0), (5.0, 1.0), (4.0, 0.8), (1.0, 0.2), (0.0, 0.0)] {
+            move_to(&mut app, boid, Vec2::new(x, 0.0));
+            assert!((goal(&app, boid).1 - throttle).abs() < 1e-6, "at {}", x);
+        }
+    }
+
+    // The slot behind and to the left of the leader turns with it
+    #[test]
+    fn leader_slots_turn_with_the_leader() {
+        let offset = Vec2::new(-4.0, -4.0);
+        // Leader headings, with the slot they put the follower in
+        for (angle, slot) in [
+            (0.0, Vec2::new(6.0, 14.0)),
+            (FRAC_PI_2, Vec2::new(6.0, 6.0)),
+        ] {
+            let mut app = app_with(1.0);
+            let leader =
+                Transform::from_xyz(10.0, 10.0, 0.0).with_rotation(Quat::from_rotation_z(angle));
+            let leader = app.world.spawn((boid(), leader)).id();
+            let position = Vec2::new(-30.0, -40.0);
+            let follower = spawn(
+                &mut app,
+                Transform::from_translation(position.extend(0.0)),
+                BoidGoal::leader(leader, offset, 1.0, 100.0),
+            );
+            app.update();
+
+            let transform = app.world.get::<Transform>(follower).unwrap();
+            let towards = (slot - position).normalize();
+            assert!(
+                heading(transform).distance(towards) < 1e-4,
+                "heading {} instead of {}",
+                heading(transform),
+                towards
+            );
+            let throttle = app.world.get::<BoidGoal>(follower).unwrap().throttle;
+            assert!((throttle - slot.distance(position) / 100.0).abs() < 1e-5);
+        }
+    }
+}
@@ -1,2 +1,7 @@
-mod boid;
-mod quadtree;
+pub mod boid;
+pub mod flock;
+pub mod goal;
+pub mod quadtree;
+pub mod replay;
+pub mod scenario;
+pub mod tint;
@@ -7,10 +7,31 @@ pub static X_EXTENT: f32 = 600.0f32;
 pub static Y_EXTENT: f32 = 400.0f32;
 const ITEM_PER_QUAD: usize = 100;
 
+// Half size of the simulated world, centered on the origin
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct WorldBounds {
+    pub half_extents: Vec2,
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec2::new(X_EXTENT, Y_EXTENT),
+        }
+    }
+}
+
+impl WorldBounds {
+    pub fn rect(&self) -> Rect {
+        Rect::from_center_half_size(Vec2::ZERO, self.half_extents)
+    }
+}
+
 pub struct QuadtreePlugin;
 impl Plugin for QuadtreePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Quadtree::new(Rect::default(), ITEM_PER_QUAD))
+        app.init_resource::<WorldBounds>()
+            .insert_resource(Quadtree::new(Rect::default(), ITEM_PER_QUAD))
             .add_systems(PreUpdate, (clean_quadtree, update_quadtree_system))
             .add_systems(Update, draw_quadtree);
     }
@@ -149,17 +170,34 @@ impl Quadtree {
             }
         }
     }
+
+    // Same as query, but returns the entities instead of their transforms
+    pub fn query_entities(&self, area: Rect, found: &mut Vec<Entity>) {
+        if self.bounds.intersect(area).is_empty() {
+            return;
+        }
+
+        if let Some(children) = &self.children {
+            for child in children.iter() {
+                child.query_entities(area, found);
+            }
+        } else {
+            for item in &self.items {
+                if area.contains(item.transform.translation.xy()) {
+                    found.push(item.entity);
+                }
+            }
+        }
+    }
 }
 
 // Bevy system to update the quadtree
 fn update_quadtree_system(
     mut quadtree: ResMut<Quadtree>,
     query: Query<(Entity, &Transform), With<QuadTreeDetect>>,
+    bounds: Res<WorldBounds>,
 ) {
-    *quadtree = Quadtree::new(
-        Rect::new(-X_EXTENT, -Y_EXTENT, X_EXTENT, Y_EXTENT),
-        ITEM_PER_QUAD,
-    );
+    *quadtree = Quadtree::new(bounds.rect(), ITEM_PER_QUAD);
 
     for (entity, transform) in query.iter() {
         quadtree.insert(entity, *transform)
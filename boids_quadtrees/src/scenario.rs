@@ -0,0 +1,369 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use serde::Deserialize;
+
+use crate::boid::{boid_sprite, Boid};
+use crate::goal::{BoidGoal, BoidPath};
+use crate::quadtree::{QuadTreeDetect, WorldBounds};
+use crate::tint::Tint;
+
+const SCENARIO_PATH: &str = "scenarios/boids.scenario.ron";
+
+pub struct ScenarioPlugin;
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Scenario>()
+            .register_asset_loader(ScenarioLoader)
+            .add_systems(Startup, load_scenario)
+            .add_systems(Update, spawn_scenario);
+    }
+}
+
+// Everything needed to set up a run, loaded from a `.scenario.ron` file.
+// Editing the file while the app runs respawns the scene.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Scenario {
+    pub bounds: Vec2,
+    pub groups: Vec<SpawnGroup>,
+    #[serde(default)]
+    pub migrations: Vec<Migration>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpawnGroup {
+    pub species: String,
+    pub count: usize,
+    pub region: Region,
+    pub heading: HeadingDistribution,
+    pub speed: f32,
+    pub rotation_speed: f32,
+}
+
+// A leader flying along a path with a V formation of followers behind it
+#[derive(Deserialize, Debug, Clone)]
+pub struct Migration {
+    pub species: String,
+    pub path: BoidPath,
+    pub followers: usize,
+    pub speed: f32,
+    // Followers have to be a bit faster than the leader to catch up with their slot
+    pub follower_speed: f32,
+    pub rotation_speed: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum Region {
+    World,
+    Rect { min: Vec2, max: Vec2 },
+    Circle { center: Vec2, radius: f32 },
+}
+
+// Angles are in radians, 0 being the spawn orientation of the sprite
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum HeadingDistribution {
+    Random,
+    Fixed(f32),
+    Cone { angle: f32, spread: f32 },
+}
+
+impl Region {
+    fn sample(&self, rgen: &mut ThreadRng, bounds: &WorldBounds) -> Vec2 {
+        match *self {
+            Region::World => Vec2::new(
+                rgen.gen_range(-bounds.half_extents.x..bounds.half_extents.x),
+                rgen.gen_range(-bounds.half_extents.y..bounds.half_extents.y),
+            ),
+            Region::Rect { min, max } => {
+                Vec2::new(rgen.gen_range(min.x..=max.x), rgen.gen_range(min.y..=max.y))
+            }
+            Region::Circle { center, radius } => {
+                // sqrt keeps the points evenly spread over the disc
+                let r = radius * rgen.gen_range(0.0f32..=1.0).sqrt();
+                center + Vec2::from_angle(rgen.gen_range(0.0..(2.0 * PI))) * r
+            }
+        }
+    }
+}
+
+impl HeadingDistribution {
+    fn sample(&self, rgen: &mut ThreadRng) -> f32 {
+        match *self {
+            HeadingDistribution::Random => rgen.gen_range(0.0..(2.0 * PI)),
+            HeadingDistribution::Fixed(angle) => angle,
+            HeadingDistribution::Cone { angle, spread } => angle + rgen.gen_range(-spread..=spread),
+        }
+    }
+}
+
+// A range in a scenario file that has nothing to pick from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangeError {
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl std::fmt::Display for RangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} goes from {} down to {}",
+            self.name, self.min, self.max
+        )
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+// Picking from min..=max panics when max is below min
+fn check_range(name: &'static str, min: f32, max: f32) -> Result<(), RangeError> {
+    if min <= max {
+        Ok(())
+    } else {
+        Err(RangeError { name, min, max })
+    }
+}
+
+impl Scenario {
+    // Rejects what would panic once spawned, so a bad hot reload only logs an error
+    // and keeps the current scene
+    pub fn validate(&self) -> Result<(), RangeError> {
+        // The world region picks from -bounds..bounds, which can't be empty
+        if !self.bounds.cmpgt(Vec2::ZERO).all() {
+            let bounds = self.bounds.min_element();
+            return Err(RangeError {
+                name: "bounds",
+                min: -bounds,
+                max: bounds,
+            });
+        }
+        for group in self.groups.iter() {
+            if let Region::Rect { min, max } = group.region {
+                check_range("rect x", min.x, max.x)?;
+                check_range("rect y", min.y, max.y)?;
+            }
+            if let HeadingDistribution::Cone { spread, .. } = group.heading {
+                check_range("cone spread", -spread, spread)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct ScenarioLoader;
+
+impl AssetLoader for ScenarioLoader {
+    type Asset = Scenario;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Scenario, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let scenario: Scenario = ron::de::from_bytes(&bytes)?;
+            scenario.validate()?;
+            Ok(scenario)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scenario.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct ScenarioAssets {
+    pub scenario: Handle<Scenario>,
+    // Texture shared by every boid
+    pub texture: Handle<Image>,
+}
+
+// Marks entities that get despawned when the scenario is reloaded
+#[derive(Component)]
+pub struct ScenarioEntity;
+
+// Name of the spawn group or migration the boid came from
+#[derive(Component, Debug, Clone)]
+pub struct Species(pub String);
+
+fn load_scenario(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ScenarioAssets {
+        scenario: asset_server.load(SCENARIO_PATH),
+        texture: asset_server.load("boid.png"),
+    });
+}
+
+fn spawn_scenario(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Scenario>>,
+    assets: Res<ScenarioAssets>,
+    scenarios: Res<Assets<Scenario>>,
+    spawned: Query<Entity, With<ScenarioEntity>>,
+    mut bounds: ResMut<WorldBounds>,
+    mut paths: ResMut<Assets<BoidPath>>,
+) {
+    // Both the first load and every hot reload end up here
+    let changed = events.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+            *id == assets.scenario.id()
+        }
+        _ => false,
+    });
+    if !changed {
+        return;
+    }
+    let Some(scenario) = scenarios.get(&assets.scenario) else {
+        return;
+    };
+
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    bounds.half_extents = scenario.bounds;
+
+    let mut rgen = thread_rng();
+    for group in scenario.groups.iter() {
+        for _ in 0..group.count {
+            let position = group.region.sample(&mut rgen, &bounds);
+            let rotation = Quat::from_rotation_z(group.heading.sample(&mut rgen));
+
+            commands.spawn((
+                boid_sprite(
+                    &assets.texture,
+                    Transform::from_translation(position.extend(0.0)).with_rotation(rotation),
+                ),
+                QuadTreeDetect,
+                Boid {
+                    rotation_speed: group.rotation_speed,
+                    speed: group.speed,
+                },
+                Tint::default(),
+                ScenarioEntity,
+                Species(group.species.clone()),
+            ));
+        }
+    }
+
+    for migration in scenario.migrations.iter() {
+        spawn_migration(&mut commands, &assets.texture, &mut paths, migration);
+    }
+}
+
+// Distance between neighbouring slots of a formation
+const FORMATION_SPACING: f32 = 4.0;
+
+fn spawn_migration(
+    commands: &mut Commands,
+    texture: &Handle<Image>,
+    paths: &mut Assets<BoidPath>,
+    migration: &Migration,
+) {
+    let Some(start) = migration.path.waypoints.first() else {
+        warn!("skipping a migration without waypoints");
+        return;
+    };
+    let start = start.extend(0.0);
+    let path = paths.add(migration.path.clone());
+
+    let leader = commands
+        .spawn((
+            boid_sprite(texture, Transform::from_translation(start)),
+            QuadTreeDetect,
+            Boid {
+                rotation_speed: migration.rotation_speed,
+                speed: migration.speed,
+            },
+            Tint::default(),
+            ScenarioEntity,
+            Species(migration.species.clone()),
+            BoidGoal::path(path, 2.0, 20.0),
+        ))
+        .id();
+
+    for i in 0..migration.followers {
+        // Alternate sides, one row further back every two boids
+        let row = (i / 2 + 1) as f32;
+        let side = if i % 2 == 0 { -1.0 } else { 1.0 };
+        let offset = Vec2::new(side * row, -row) * FORMATION_SPACING;
+
+        commands.spawn((
+            boid_sprite(
+                texture,
+                Transform::from_translation(start + offset.extend(0.0)),
+            ),
+            QuadTreeDetect,
+            Boid {
+                rotation_speed: migration.rotation_speed,
+                speed: migration.follower_speed,
+            },
+            Tint::default(),
+            ScenarioEntity,
+            Species(migration.species.clone()),
+            BoidGoal::leader(leader, offset, 4.0, 10.0),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_group(group: &str) -> Scenario {
+        let source = format!(
+            "(
+                bounds: (100.0, 100.0),
+                groups: [(species: \"test\", count: 10, speed: 10.0, rotation_speed: 1.0, {group})],
+            )"
+        );
+        ron::from_str(&source).unwrap()
+    }
+
+    #[test]
+    fn shipped_scenario_is_valid() {
+        let scenario: Scenario =
+            ron::from_str(include_str!("../assets/scenarios/boids.scenario.ron")).unwrap();
+        scenario.validate().unwrap();
+    }
+
+    // Ranges `gen_range` would panic on are refused before anything gets spawned
+    #[test]
+    fn inverted_ranges_are_rejected() {
+        let valid = with_group(
+            "region: Rect(min: (-5.0, -5.0), max: (5.0, 5.0)), heading: Cone(angle: 1.0, spread: 0.0)",
+        );
+        assert!(valid.validate().is_ok());
+
+        let inverted =
+            with_group("region: Rect(min: (-5.0, 5.0), max: (5.0, -5.0)), heading: Random");
+        assert_eq!(
+            inverted.validate(),
+            Err(RangeError {
+                name: "rect y",
+                min: 5.0,
+                max: -5.0
+            })
+        );
+        let backwards = with_group("region: World, heading: Cone(angle: 1.0, spread: -0.5)");
+        assert!(backwards.validate().is_err());
+
+        let empty = Scenario {
+            bounds: Vec2::new(0.0, 100.0),
+            ..valid
+        };
+        assert!(empty.validate().is_err());
+    }
+}
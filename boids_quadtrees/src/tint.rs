@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+
+use crate::boid::Boid;
+use crate::goal::{heading, BoidGoal};
+
+// Speed that maps to the end of the speed colour ramp
+const SPEED_RANGE: f32 = 40.0;
+
+pub struct TintPlugin;
+impl Plugin for TintPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TintMode>().add_systems(
+            Update,
+            (
+                cycle_tint_mode,
+                tint_by_heading.run_if(resource_equals(TintMode::Heading)),
+                tint_by_speed.run_if(resource_equals(TintMode::Speed)),
+                apply_tint,
+            )
+                .chain(),
+        );
+    }
+}
+
+// Colour of an entity, copied into its sprite. Every entity shares the same
+// texture so the sprites batch together no matter how they are tinted.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Tint(pub Color);
+
+impl Default for Tint {
+    fn default() -> Self {
+        Self(Color::WHITE)
+    }
+}
+
+// What drives the tint of the boids, cycled with T
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TintMode {
+    #[default]
+    Flock,
+    Heading,
+    Speed,
+}
+
+fn cycle_tint_mode(mut mode: ResMut<TintMode>, keyboard_input: Res<ButtonInput<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::KeyT) {
+        *mode = match *mode {
+            TintMode::Flock => TintMode::Heading,
+            TintMode::Heading => TintMode::Speed,
+            TintMode::Speed => TintMode::Flock,
+        };
+    }
+}
+
+fn tint_by_heading(mut query: Query<(&Transform, &mut Tint), With<Boid>>) {
+    for (transform, mut tint) in query.iter_mut() {
+        let direction = heading(transform);
+        let hue = direction
+            .y
+            .atan2(direction.x)
+            .to_degrees()
+            .rem_euclid(360.0);
+        tint.0 = Color::hsl(hue, 0.8, 0.6);
+    }
+}
+
+fn tint_by_speed(mut query: Query<(&Boid, Option<&BoidGoal>, &mut Tint)>) {
+    for (boid, goal, mut tint) in query.iter_mut() {
+        let speed = boid.speed * goal.map_or(1.0, |goal| goal.throttle);
+        // Blue when slow, red when fast
+        let t = (speed / SPEED_RANGE).clamp(0.0, 1.0);
+        tint.0 = Color::hsl(240.0 * (1.0 - t), 0.8, 0.6);
+    }
+}
+
+pub fn apply_tint(mut query: Query<(&Tint, &mut Sprite), Changed<Tint>>) {
+    for (tint, mut sprite) in query.iter_mut() {
+        sprite.color = tint.0;
+    }
+}
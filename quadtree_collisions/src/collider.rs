@@ -0,0 +1,597 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+pub const MAX_POLYGON_VERTICES: usize = 8;
+
+// Shape a body collides with. Bodies without one are circles of their collider radius.
+#[derive(Component, Deserialize, Debug, Clone, PartialEq)]
+pub enum ColliderShape {
+    Circle { radius: f32 },
+    // Box lined up with the axes
+    Aabb { half_extents: Vec2 },
+    // Box turned by `angle` radians
+    Obb { half_extents: Vec2, angle: f32 },
+    // Convex outline around the body position, up to MAX_POLYGON_VERTICES points
+    ConvexPolygon { vertices: Vec<Vec2> },
+}
+
+impl ColliderShape {
+    pub fn shape(&self) -> Shape {
+        match self {
+            ColliderShape::Circle { .. } => Shape::Circle,
+            ColliderShape::Aabb { half_extents } => {
+                Shape::Polygon(Polygon::rectangle(*half_extents, 0.0))
+            }
+            ColliderShape::Obb {
+                half_extents,
+                angle,
+            } => Shape::Polygon(Polygon::rectangle(*half_extents, *angle)),
+            // The scenario loader rejects outlines that aren't convex polygons
+            ColliderShape::ConvexPolygon { vertices } => {
+                Polygon::new(vertices).map_or(Shape::Circle, Shape::Polygon)
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), PolygonError> {
+        match self {
+            ColliderShape::ConvexPolygon { vertices } => Polygon::new(vertices).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    // Radius of the circle around the body position holding the whole shape
+    pub fn bounding_radius(&self) -> f32 {
+        match (self, self.shape()) {
+            (ColliderShape::Circle { radius }, _) => *radius,
+            (_, Shape::Polygon(polygon)) => polygon.bounding_radius(),
+            (_, Shape::Circle) => 0.0,
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        match (self, self.shape()) {
+            (ColliderShape::Circle { radius }, _) => radius * radius * std::f32::consts::PI,
+            (_, Shape::Polygon(polygon)) => polygon.area(),
+            (_, Shape::Circle) => 0.0,
+        }
+    }
+
+    // Around the body position, for `mass` spread evenly over the shape
+    pub fn moment_of_inertia(&self, mass: f32) -> f32 {
+        match (self, self.shape()) {
+            (ColliderShape::Circle { radius }, _) => circle_inertia(mass, *radius),
+            (_, Shape::Polygon(polygon)) => polygon.moment_of_inertia(mass),
+            (_, Shape::Circle) => 0.0,
+        }
+    }
+
+    // Turn built into the shape, on top of the angle of its body
+    pub fn angle(&self) -> f32 {
+        match self {
+            ColliderShape::Obb { angle, .. } => *angle,
+            _ => 0.0,
+        }
+    }
+}
+
+pub fn circle_inertia(mass: f32, radius: f32) -> f32 {
+    mass * radius * radius / 2.0
+}
+
+// Shape as the solver sees it, circles use the radius of the body
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Circle,
+    Polygon(Polygon),
+}
+
+impl Shape {
+    pub fn rotated(&self, angle: f32) -> Shape {
+        match self {
+            Shape::Polygon(polygon) if angle != 0.0 => Shape::Polygon(polygon.rotated(angle)),
+            _ => *self,
+        }
+    }
+}
+
+// Counter-clockwise vertices relative to the body position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Polygon {
+    vertices: [Vec2; MAX_POLYGON_VERTICES],
+    len: usize,
+}
+
+// Why a list of points doesn't make a polygon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolygonError {
+    TooFewPoints(usize),
+    TooManyPoints(usize),
+    NotConvex,
+}
+
+impl std::fmt::Display for PolygonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PolygonError::TooFewPoints(count) => {
+                write!(f, "a polygon needs at least 3 points, got {count}")
+            }
+            PolygonError::TooManyPoints(count) => {
+                write!(
+                    f,
+                    "a polygon can have at most {MAX_POLYGON_VERTICES} points, got {count}"
+                )
+            }
+            PolygonError::NotConvex => write!(f, "polygon points don't form a convex outline"),
+        }
+    }
+}
+
+impl std::error::Error for PolygonError {}
+
+impl Polygon {
+    // Every vertex has to turn the outline the same way, points in line with
+    // their neighbours or repeated are rejected too
+    pub fn new(points: &[Vec2]) -> Result<Self, PolygonError> {
+        if points.len() < 3 {
+            return Err(PolygonError::TooFewPoints(points.len()));
+        }
+        if points.len() > MAX_POLYGON_VERTICES {
+            return Err(PolygonError::TooManyPoints(points.len()));
+        }
+
+        // Sorted by angle around their centre, so a convex outline can be given in any order
+        let centre = points.iter().copied().sum::<Vec2>() / points.len().max(1) as f32;
+        let mut sorted = points.to_vec();
+        sorted.sort_by(|a, b| {
+            let a = *a - centre;
+            let b = *b - centre;
+            a.y.atan2(a.x).total_cmp(&b.y.atan2(b.x))
+        });
+
+        let convex = (0..sorted.len()).all(|i| {
+            let (a, b, c) = (
+                sorted[i],
+                sorted[(i + 1) % sorted.len()],
+                sorted[(i + 2) % sorted.len()],
+            );
+            (b - a).perp_dot(c - b) > f32::EPSILON
+        });
+        if !convex {
+            return Err(PolygonError::NotConvex);
+        }
+        Ok(Self::from_sorted(&sorted))
+    }
+
+    // `points` already go counter-clockwise
+    fn from_sorted(points: &[Vec2]) -> Self {
+        let mut vertices = [Vec2::ZERO; MAX_POLYGON_VERTICES];
+        vertices[..points.len()].copy_from_slice(points);
+        Self {
+            vertices,
+            len: points.len(),
+        }
+    }
+
+    // Flat two sided polygon from `-half` to `half`, for colliding with walls
+    pub fn segment(half: Vec2) -> Self {
+        Self::from_sorted(&[-half, half])
+    }
+
+    pub fn rectangle(half_extents: Vec2, angle: f32) -> Self {
+        let rotation = Vec2::from_angle(angle);
+        let corners = [
+            Vec2::new(-half_extents.x, -half_extents.y),
+            Vec2::new(half_extents.x, -half_extents.y),
+            Vec2::new(half_extents.x, half_extents.y),
+            Vec2::new(-half_extents.x, half_extents.y),
+        ];
+        Self::from_sorted(&corners.map(|corner| rotation.rotate(corner)))
+    }
+
+    pub fn vertices(&self) -> &[Vec2] {
+        &self.vertices[..self.len]
+    }
+
+    pub fn bounding_radius(&self) -> f32 {
+        self.vertices()
+            .iter()
+            .map(|vertex| vertex.length())
+            .fold(0.0, f32::max)
+    }
+
+    pub fn area(&self) -> f32 {
+        let vertices = self.vertices();
+        let twice_area: f32 = (0..vertices.len())
+            .map(|i| vertices[i].perp_dot(vertices[(i + 1) % vertices.len()]))
+            .sum();
+        twice_area.abs() / 2.0
+    }
+
+    pub fn moment_of_inertia(&self, mass: f32) -> f32 {
+        // Sum over the triangles fanning out from the body position
+        let vertices = self.vertices();
+        let (mut weighted, mut total) = (0.0, 0.0);
+        for i in 0..vertices.len() {
+            let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+            let cross = a.perp_dot(b);
+            weighted += cross * (a.dot(a) + a.dot(b) + b.dot(b));
+            total += cross;
+        }
+        if total.abs() <= f32::EPSILON {
+            return 0.0;
+        }
+        mass * weighted / (6.0 * total)
+    }
+
+    pub fn rotated(&self, angle: f32) -> Self {
+        let rotation = Vec2::from_angle(angle);
+        let mut rotated = *self;
+        for vertex in rotated.vertices[..self.len].iter_mut() {
+            *vertex = rotation.rotate(*vertex);
+        }
+        rotated
+    }
+
+    // Vertices placed at `position`
+    pub fn at(&self, position: Vec2) -> Vec<Vec2> {
+        self.vertices()
+            .iter()
+            .map(|vertex| position + *vertex)
+            .collect()
+    }
+}
+
+// Outward normal of the edge starting at vertex `i`
+fn edge_normal(vertices: &[Vec2], i: usize) -> Vec2 {
+    let edge = vertices[(i + 1) % vertices.len()] - vertices[i];
+    Vec2::new(edge.y, -edge.x).normalize_or_zero()
+}
+
+// Where two shapes touch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Manifold {
+    // Points from the first shape to the second
+    pub normal: Vec2,
+    points: [Vec2; 2],
+    depths: [f32; 2],
+    len: usize,
+}
+
+impl Manifold {
+    fn single(normal: Vec2, point: Vec2, depth: f32) -> Self {
+        Self {
+            normal,
+            points: [point, point],
+            depths: [depth, depth],
+            len: 1,
+        }
+    }
+
+    fn flipped(mut self) -> Self {
+        self.normal = -self.normal;
+        self
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points[..self.len]
+    }
+
+    pub fn depths(&self) -> &[f32] {
+        &self.depths[..self.len]
+    }
+
+    pub fn depth(&self) -> f32 {
+        self.depths().iter().copied().fold(0.0, f32::max)
+    }
+
+    pub fn centre(&self) -> Vec2 {
+        self.points().iter().copied().sum::<Vec2>() / self.len as f32
+    }
+}
+
+// Contact between two shapes, the first at the origin and the second at `offset`.
+// The manifold points are relative to the first shape too.
+pub fn collide(
+    shape1: &Shape,
+    radius1: f32,
+    shape2: &Shape,
+    radius2: f32,
+    offset: Vec2,
+) -> Option<Manifold> {
+    match (shape1, shape2) {
+        (Shape::Circle, Shape::Circle) => {
+            let distance = offset.length();
+            let depth = radius1 + radius2 - distance;
+            if depth < 0.0 || distance <= f32::EPSILON {
+                return None;
+            }
+            let normal = offset / distance;
+            Some(Manifold::single(normal, normal * radius1, depth))
+        }
+        (Shape::Polygon(polygon), Shape::Circle) => {
+            polygon_circle(&polygon.at(Vec2::ZERO), offset, radius2)
+        }
+        (Shape::Circle, Shape::Polygon(polygon)) => {
+            polygon_circle(&polygon.at(offset), Vec2::ZERO, radius1).map(Manifold::flipped)
+        }
+        (Shape::Polygon(polygon1), Shape::Polygon(polygon2)) => {
+            polygon_polygon(&polygon1.at(Vec2::ZERO), &polygon2.at(offset))
+        }
+    }
+}
+
+// Normal goes from the polygon to the circle
+fn polygon_circle(vertices: &[Vec2], centre: Vec2, radius: f32) -> Option<Manifold> {
+    // Face the centre is the furthest in front of
+    let mut separation = f32::MIN;
+    let mut face = 0;
+    for i in 0..vertices.len() {
+        let distance = edge_normal(vertices, i).dot(centre - vertices[i]);
+        if distance > radius {
+            return None;
+        }
+        if distance > separation {
+            separation = distance;
+            face = i;
+        }
+    }
+
+    let normal = edge_normal(vertices, face);
+    let start = vertices[face];
+    let end = vertices[(face + 1) % vertices.len()];
+    if separation <= 0.0 {
+        // Centre inside the polygon
+        return Some(Manifold::single(
+            normal,
+            centre - normal * separation,
+            radius - separation,
+        ));
+    }
+
+    // Closer to a corner than to the face
+    for (corner, other) in [(start, end), (end, start)] {
+        if (centre - corner).dot(other - corner) <= 0.0 {
+            let distance = centre.distance(corner);
+            if distance > radius {
+                return None;
+            }
+            let normal = (centre - corner).try_normalize().unwrap_or(normal);
+            return Some(Manifold::single(normal, corner, radius - distance));
+        }
+    }
+
+    Some(Manifold::single(
+        normal,
+        centre - normal * separation,
+        radius - separation,
+    ))
+}
+
+// Largest distance a face of `a` puts between itself and all of `b`, negative when overlapping
+fn max_separation(a: &[Vec2], b: &[Vec2]) -> (f32, usize) {
+    let mut best = (f32::MIN, 0);
+    for i in 0..a.len() {
+        let normal = edge_normal(a, i);
+        let separation = b
+            .iter()
+            .map(|vertex| normal.dot(*vertex - a[i]))
+            .fold(f32::MAX, f32::min);
+        if separation > best.0 {
+            best = (separation, i);
+        }
+    }
+    best
+}
+
+// Keeps the part of the segment where normal · point <= offset
+fn clip_segment(points: [Vec2; 2], normal: Vec2, offset: f32) -> Option<[Vec2; 2]> {
+    let distance0 = normal.dot(points[0]) - offset;
+    let distance1 = normal.dot(points[1]) - offset;
+    match (distance0 <= 0.0, distance1 <= 0.0) {
+        (true, true) => Some(points),
+        (false, false) => None,
+        (inside0, _) => {
+            let t = distance0 / (distance0 - distance1);
+            let crossing = points[0].lerp(points[1], t);
+            Some(if inside0 {
+                [points[0], crossing]
+            } else {
+                [crossing, points[1]]
+            })
+        }
+    }
+}
+
+// Separating axis test, then the incident edge clipped against the reference face
+fn polygon_polygon(a: &[Vec2], b: &[Vec2]) -> Option<Manifold> {
+    let (separation_a, face_a) = max_separation(a, b);
+    if separation_a > 0.0 {
+        return None;
+    }
+    let (separation_b, face_b) = max_separation(b, a);
+    if separation_b > 0.0 {
+        return None;
+    }
+
+    // Prefer faces of `a` so the manifold doesn't flicker between nearly equal axes
+    let flip = separation_b > separation_a + 1e-3;
+    let (reference, incident, face) = if flip { (b, a, face_b) } else { (a, b, face_a) };
+    let normal = edge_normal(reference, face);
+
+    // Edge of the other polygon facing the reference face the most
+    let incident_face = (0..incident.len())
+        .min_by(|&i, &j| {
+            edge_normal(incident, i)
+                .dot(normal)
+                .total_cmp(&edge_normal(incident, j).dot(normal))
+        })
+        .unwrap_or(0);
+    let incident_edge = [
+        incident[incident_face],
+        incident[(incident_face + 1) % incident.len()],
+    ];
+
+    let start = reference[face];
+    let end = reference[(face + 1) % reference.len()];
+    let tangent = (end - start).normalize_or_zero();
+    let clipped = clip_segment(incident_edge, -tangent, -tangent.dot(start))?;
+    let clipped = clip_segment(clipped, tangent, tangent.dot(end))?;
+
+    let mut manifold = Manifold {
+        normal: if flip { -normal } else { normal },
+        points: [Vec2::ZERO; 2],
+        depths: [0.0; 2],
+        len: 0,
+    };
+    for point in clipped {
+        let separation = normal.dot(point - start);
+        if separation <= 0.0 {
+            manifold.points[manifold.len] = point;
+            manifold.depths[manifold.len] = -separation;
+            manifold.len += 1;
+        }
+    }
+    (manifold.len > 0).then_some(manifold)
+}
+
+pub fn draw_colliders(mut gizmos: Gizmos, query: Query<(&ColliderShape, &Transform)>) {
+    for (shape, transform) in query.iter() {
+        let Shape::Polygon(polygon) = shape.shape() else {
+            continue;
+        };
+        // The transform is turned by the shape's own angle too, which the vertices already have
+        let rotation = transform.rotation * Quat::from_rotation_z(-shape.angle());
+        let mut outline: Vec<Vec2> = polygon
+            .vertices()
+            .iter()
+            .map(|vertex| transform.translation.xy() + (rotation * vertex.extend(0.0)).xy())
+            .collect();
+        if let Some(&first) = outline.first() {
+            outline.push(first);
+        }
+        gizmos.linestrip_2d(outline, Color::WHITE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::tests::{run, shaped};
+    use crate::physics::PhysicsSettings;
+
+    #[test]
+    fn overlapping_boxes_touch_along_a_face() {
+        let square = ColliderShape::Aabb {
+            half_extents: Vec2::splat(2.0),
+        };
+        let manifold = collide(
+            &square.shape(),
+            square.bounding_radius(),
+            &square.shape(),
+            square.bounding_radius(),
+            Vec2::new(3.0, 0.5),
+        )
+        .expect("overlapping boxes not detected");
+        assert!(manifold.normal.abs_diff_eq(Vec2::X, 1e-5));
+        assert!((manifold.depth() - 1.0).abs() < 1e-5);
+        assert_eq!(manifold.points().len(), 2, "face contact needs two points");
+    }
+
+    // Circle resting on the top corner of a box turned into a diamond
+    #[test]
+    fn circles_touch_polygon_corners() {
+        let diamond = ColliderShape::Obb {
+            half_extents: Vec2::splat(2.0),
+            angle: std::f32::consts::FRAC_PI_4,
+        };
+        let corner = 2.0 * std::f32::consts::SQRT_2;
+        let manifold = collide(
+            &Shape::Circle,
+            1.0,
+            &diamond.shape(),
+            diamond.bounding_radius(),
+            Vec2::new(0.0, -corner - 0.5),
+        )
+        .expect("circle on a corner not detected");
+        assert!(manifold.normal.abs_diff_eq(-Vec2::Y, 1e-4));
+        assert!((manifold.depth() - 0.5).abs() < 1e-4);
+        assert!(collide(
+            &Shape::Circle,
+            1.0,
+            &diamond.shape(),
+            diamond.bounding_radius(),
+            Vec2::new(0.0, -corner - 1.5),
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn overlapping_boxes_get_pushed_apart() {
+        let settings = PhysicsSettings {
+            gravity: false,
+            sleeping: false,
+            ..default()
+        };
+        let square = ColliderShape::Aabb {
+            half_extents: Vec2::splat(2.0),
+        };
+        let mut bodies = [
+            shaped(0, Vec2::new(-1.5, 0.0), &square),
+            shaped(1, Vec2::new(1.5, 0.0), &square),
+        ];
+        run(&mut bodies, &settings, 10);
+        let gap = bodies[1].position.x - bodies[0].position.x;
+        assert!(gap >= 4.0 - 1e-3, "boxes still overlap, {} apart", gap);
+    }
+
+    #[test]
+    fn polygons_need_a_convex_outline() {
+        let pentagon = [
+            Vec2::new(-6.0, -4.0),
+            Vec2::new(6.0, -5.0),
+            Vec2::new(8.0, 2.0),
+            Vec2::new(0.0, 7.0),
+            Vec2::new(-7.0, 3.0),
+        ];
+        // Any order of the same points gives the same outline
+        let mut shuffled = pentagon;
+        shuffled.reverse();
+        assert_eq!(Polygon::new(&pentagon), Polygon::new(&shuffled));
+        assert!(Polygon::new(&pentagon).is_ok());
+
+        assert_eq!(
+            Polygon::new(&[Vec2::ZERO, Vec2::X]),
+            Err(PolygonError::TooFewPoints(2))
+        );
+        assert_eq!(Polygon::new(&[]), Err(PolygonError::TooFewPoints(0)));
+        // A dent pointing back at the centre
+        let dart = [
+            Vec2::new(-4.0, -4.0),
+            Vec2::new(0.0, -1.0),
+            Vec2::new(4.0, -4.0),
+            Vec2::new(0.0, 4.0),
+        ];
+        assert_eq!(Polygon::new(&dart), Err(PolygonError::NotConvex));
+        let in_line = [Vec2::ZERO, Vec2::X, Vec2::X * 2.0];
+        assert_eq!(Polygon::new(&in_line), Err(PolygonError::NotConvex));
+        // A circle of points past the limit isn't cut down to its first few
+        let round: Vec<Vec2> = (0..MAX_POLYGON_VERTICES + 1)
+            .map(|i| {
+                Vec2::from_angle(
+                    i as f32 / (MAX_POLYGON_VERTICES + 1) as f32 * std::f32::consts::TAU,
+                ) * 5.0
+            })
+            .collect();
+        assert_eq!(
+            Polygon::new(&round),
+            Err(PolygonError::TooManyPoints(MAX_POLYGON_VERTICES + 1))
+        );
+        assert!(Polygon::new(&round[..MAX_POLYGON_VERTICES]).is_ok());
+
+        let shape = ColliderShape::ConvexPolygon {
+            vertices: dart.to_vec(),
+        };
+        assert_eq!(shape.validate(), Err(PolygonError::NotConvex));
+    }
+}
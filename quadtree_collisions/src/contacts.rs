@@ -0,0 +1,152 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::physics::update_physics;
+use crate::replay::SimMode;
+
+pub struct ContactPlugin;
+impl Plugin for ContactPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CollisionStarted>()
+            .add_event::<CollisionOngoing>()
+            .add_event::<CollisionEnded>()
+            .init_resource::<StepContacts>()
+            .init_resource::<ActiveContacts>()
+            .add_systems(
+                FixedUpdate,
+                send_collision_events
+                    .after(update_physics)
+                    .run_if(in_state(SimMode::Live)),
+            );
+    }
+}
+
+// Two bodies, or a body and a segment, touching during a physics step
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    pub entities: [Entity; 2],
+    pub point: Vec3,
+    // Points from the first entity to the second
+    pub normal: Vec3,
+    // Total normal impulse over the step, 0 for bodies resting against each other
+    pub impulse: f32,
+    // How far they overlapped the last time the solver looked at them
+    pub depth: f32,
+}
+
+impl Contact {
+    // Same for both orders of the entities
+    pub fn key(&self) -> [Entity; 2] {
+        let [a, b] = self.entities;
+        [a.min(b), a.max(b)]
+    }
+}
+
+// First step two bodies touch
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CollisionStarted(pub Contact);
+
+// Every following step they still touch
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CollisionOngoing(pub Contact);
+
+// First step they don't touch anymore, carrying the last contact seen
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CollisionEnded(pub Contact);
+
+// Contacts found by the last physics step
+#[derive(Resource, Default)]
+pub struct StepContacts(pub Vec<Contact>);
+
+// Contacts that have started but not ended yet
+#[derive(Resource, Default)]
+pub struct ActiveContacts(pub HashMap<[Entity; 2], Contact>);
+
+pub fn send_collision_events(
+    mut step_contacts: ResMut<StepContacts>,
+    mut active: ResMut<ActiveContacts>,
+    mut started: EventWriter<CollisionStarted>,
+    mut ongoing: EventWriter<CollisionOngoing>,
+    mut ended: EventWriter<CollisionEnded>,
+) {
+    let mut touching: HashMap<[Entity; 2], Contact> = HashMap::new();
+    for contact in step_contacts.0.drain(..) {
+        let key = contact.key();
+        if active.0.remove(&key).is_some() {
+            ongoing.send(CollisionOngoing(contact));
+        } else {
+            started.send(CollisionStarted(contact));
+        }
+        touching.insert(key, contact);
+    }
+
+    // Whatever is left was not touching in this step
+    for (_, contact) in active.0.drain() {
+        ended.send(CollisionEnded(contact));
+    }
+    active.0 = touching;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(a: u32, b: u32) -> Contact {
+        Contact {
+            entities: [Entity::from_raw(a), Entity::from_raw(b)],
+            point: Vec3::ZERO,
+            normal: Vec3::X,
+            impulse: 1.0,
+            depth: 0.0,
+        }
+    }
+
+    // Runs the system once for the contacts of one step, returning the keys of the
+    // started, ongoing and ended events it sent
+    fn send(app: &mut App, contacts: Vec<Contact>) -> [Vec<[Entity; 2]>; 3] {
+        app.world.resource_mut::<StepContacts>().0 = contacts;
+        app.update();
+        fn keys<E: Event>(app: &App, contact: impl Fn(&E) -> Contact) -> Vec<[Entity; 2]> {
+            let mut keys: Vec<[Entity; 2]> = app
+                .world
+                .resource::<Events<E>>()
+                .iter_current_update_events()
+                .map(|event| contact(event).key())
+                .collect();
+            keys.sort();
+            keys
+        }
+        [
+            keys(app, |event: &CollisionStarted| event.0),
+            keys(app, |event: &CollisionOngoing| event.0),
+            keys(app, |event: &CollisionEnded| event.0),
+        ]
+    }
+
+    // A pair touching for two steps starts, goes on and then ends, while another
+    // pair of the same body starting in the second step is reported on its own
+    #[test]
+    fn collisions_start_go_on_and_end() {
+        let mut app = App::new();
+        app.add_event::<CollisionStarted>()
+            .add_event::<CollisionOngoing>()
+            .add_event::<CollisionEnded>()
+            .init_resource::<StepContacts>()
+            .init_resource::<ActiveContacts>()
+            .add_systems(Update, send_collision_events);
+        let (first, second) = (contact(0, 1), contact(2, 0));
+
+        assert_eq!(
+            send(&mut app, vec![first]),
+            [vec![first.key()], vec![], vec![]]
+        );
+        assert_eq!(
+            send(&mut app, vec![second, contact(1, 0)]),
+            [vec![second.key()], vec![first.key()], vec![]]
+        );
+        assert_eq!(
+            send(&mut app, vec![]),
+            [vec![], vec![], vec![first.key(), second.key()]]
+        );
+        assert!(app.world.resource::<ActiveContacts>().0.is_empty());
+    }
+}
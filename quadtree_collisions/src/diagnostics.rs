@@ -0,0 +1,189 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::*,
+};
+
+use crate::contacts::{send_collision_events, Contact, StepContacts};
+use crate::gravity::{gravity_potential, Attractor, GravityField, GravitySettings, PointAttractor};
+use crate::material::PhysicsMaterial;
+use crate::physics::{update_physics, Body, Physics, PhysicsSettings, RigidBodyKind};
+use crate::quad_trees::{QuadTreeDetect, Quadtree};
+use crate::replay::SimMode;
+
+pub struct PhysicsDiagnosticsPlugin;
+
+impl PhysicsDiagnosticsPlugin {
+    pub const KINETIC_ENERGY: DiagnosticPath = DiagnosticPath::const_new("physics/kinetic_energy");
+    pub const POTENTIAL_ENERGY: DiagnosticPath =
+        DiagnosticPath::const_new("physics/potential_energy");
+    pub const MOMENTUM: DiagnosticPath = DiagnosticPath::const_new("physics/momentum");
+    pub const MAX_PENETRATION: DiagnosticPath =
+        DiagnosticPath::const_new("physics/max_penetration");
+
+    pub const ALL: [DiagnosticPath; 4] = [
+        Self::KINETIC_ENERGY,
+        Self::POTENTIAL_ENERGY,
+        Self::MOMENTUM,
+        Self::MAX_PENETRATION,
+    ];
+}
+
+impl Plugin for PhysicsDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        for path in Self::ALL {
+            app.register_diagnostic(Diagnostic::new(path));
+        }
+        app.add_systems(
+            FixedUpdate,
+            measure_step
+                .after(update_physics)
+                .before(send_collision_events)
+                .run_if(in_state(SimMode::Live)),
+        );
+    }
+}
+
+// Totals over the dynamic bodies after a step
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StepStats {
+    pub kinetic_energy: f32,
+    // 0 without gravity
+    pub potential_energy: f32,
+    pub momentum: Vec3,
+    pub max_penetration: f32,
+}
+
+pub fn measure(
+    bodies: &[Body],
+    contacts: &[Contact],
+    quadtree: &Quadtree,
+    gravity: &GravityField,
+) -> StepStats {
+    let mut stats = StepStats::default();
+    for body in bodies.iter().filter(|body| body.is_dynamic()) {
+        stats.kinetic_energy += 0.5 * body.mass * body.velocity.length_squared();
+        stats.momentum += body.velocity * body.mass;
+    }
+    stats.potential_energy = gravity_potential(bodies, quadtree, gravity);
+    stats.max_penetration = contacts
+        .iter()
+        .map(|contact| contact.depth)
+        .fold(0.0, f32::max);
+    stats
+}
+
+fn measure_step(
+    mut diagnostics: Diagnostics,
+    query: Query<(Entity, &Physics, Option<&RigidBodyKind>), With<QuadTreeDetect>>,
+    step_contacts: Res<StepContacts>,
+    quadtree: Res<Quadtree>,
+    settings: Res<PhysicsSettings>,
+    gravity: Res<GravitySettings>,
+    attractors: Query<(&Attractor, &Transform)>,
+) {
+    let bodies: Vec<Body> = query
+        .iter()
+        .map(|(entity, physics, kind)| {
+            Body::from_physics(
+                entity,
+                physics,
+                None,
+                PhysicsMaterial::default(),
+                kind.copied().unwrap_or_default(),
+            )
+        })
+        .collect();
+    let attractors: Vec<PointAttractor> = attractors
+        .iter()
+        .map(|(attractor, transform)| attractor.at(transform.translation))
+        .collect();
+    let gravity = if settings.gravity {
+        GravityField {
+            settings: *gravity,
+            attractors: &attractors,
+        }
+    } else {
+        GravityField::NONE
+    };
+    let stats = measure(&bodies, &step_contacts.0, &quadtree, &gravity);
+
+    diagnostics.add_measurement(&PhysicsDiagnosticsPlugin::KINETIC_ENERGY, || {
+        stats.kinetic_energy as f64
+    });
+    diagnostics.add_measurement(&PhysicsDiagnosticsPlugin::POTENTIAL_ENERGY, || {
+        stats.potential_energy as f64
+    });
+    diagnostics.add_measurement(&PhysicsDiagnosticsPlugin::MOMENTUM, || {
+        stats.momentum.length() as f64
+    });
+    diagnostics.add_measurement(&PhysicsDiagnosticsPlugin::MAX_PENETRATION, || {
+        stats.max_penetration as f64
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::physics::tests::{body, bounds};
+    use crate::physics::{step, Boundary};
+
+    // A crowd of particles in a wrapping box, where only their collisions change their velocity
+    #[test]
+    fn momentum_is_conserved_in_closed_box() {
+        let settings = PhysicsSettings {
+            gravity: false,
+            boundary: Boundary::Wrap,
+            // Falling asleep drops the last bit of velocity
+            sleeping: false,
+            ..default()
+        };
+        let bounds = bounds(50.0);
+        let mut rgen = StdRng::seed_from_u64(7);
+        let mut bodies: Vec<Body> = (0..400)
+            .map(|i| {
+                body(
+                    i,
+                    Vec2::new(rgen.gen_range(-50.0..50.0), rgen.gen_range(-50.0..50.0)),
+                    Vec2::new(rgen.gen_range(-200.0..200.0), rgen.gen_range(-200.0..200.0)),
+                    rgen.gen_range(0.5..3.0),
+                )
+            })
+            .collect();
+
+        let mut quadtree = Quadtree::default();
+        let start = measure(&bodies, &[], &quadtree, &GravityField::NONE);
+        // Scale of the momenta that cancel out in the total
+        let scale: f32 = bodies
+            .iter()
+            .map(|body| body.mass * body.velocity.length())
+            .sum();
+
+        let mut contacts = 0;
+        for _ in 0..200 {
+            let step_contacts = step(
+                &mut bodies,
+                &[],
+                &[],
+                &GravityField::NONE,
+                &mut quadtree,
+                &bounds,
+                &settings,
+            )
+            .contacts;
+            contacts += step_contacts.len();
+
+            let stats = measure(&bodies, &step_contacts, &quadtree, &GravityField::NONE);
+            let error = (stats.momentum - start.momentum).length() / scale;
+            assert!(
+                error < 1e-4,
+                "momentum changed by {:.2e} of its scale: {:?} -> {:?}",
+                error,
+                start.momentum,
+                stats.momentum
+            );
+        }
+        assert!(contacts > 0, "the particles never touched");
+    }
+}
@@ -0,0 +1,285 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use rand::thread_rng;
+use serde::Deserialize;
+
+use crate::material::PhysicsMaterial;
+use crate::physics::{update_physics, Physics, RigidBodyKind};
+use crate::quad_trees::WorldBounds;
+use crate::replay::SimMode;
+use crate::scenario::{
+    spawn_particle, RadiusDistribution, Region, ScenarioAssets, VelocityDistribution,
+};
+
+pub struct EmitterPlugin;
+impl Plugin for EmitterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                emit.before(update_physics),
+                (drain_sinks, expire).after(update_physics),
+            )
+                .run_if(in_state(SimMode::Live)),
+        )
+        .add_systems(Update, draw_regions);
+    }
+}
+
+// Keeps spawning bodies in its region while the simulation runs
+#[derive(Component, Deserialize, Debug, Clone)]
+pub struct Emitter {
+    pub species: String,
+    // Bodies per second
+    pub rate: f32,
+    pub region: Region,
+    pub velocity: VelocityDistribution,
+    pub radius: RadiusDistribution,
+    // Defaults to the area of each body
+    #[serde(default)]
+    pub mass: Option<f32>,
+    #[serde(default)]
+    pub material: Option<PhysicsMaterial>,
+    // Seconds before an emitted body despawns, forever when missing
+    #[serde(default)]
+    pub lifetime: Option<f32>,
+    // Fraction of a body carried over to the next step
+    #[serde(skip)]
+    pub pending: f32,
+}
+
+// Despawns the dynamic bodies whose centre enters its region
+#[derive(Component, Deserialize, Debug, Clone, Copy)]
+pub struct Sink {
+    pub region: Region,
+}
+
+// Seconds left before the body despawns
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Lifetime(pub f32);
+
+fn emit(
+    mut commands: Commands,
+    mut emitters: Query<&mut Emitter>,
+    assets: Option<Res<ScenarioAssets>>,
+    bounds: Res<WorldBounds>,
+    time: Res<Time>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    let mut rgen = thread_rng();
+    for mut emitter in emitters.iter_mut() {
+        emitter.pending += emitter.rate * time.delta_seconds();
+        while emitter.pending >= 1.0 {
+            emitter.pending -= 1.0;
+
+            let radius = emitter.radius.sample(&mut rgen);
+            let mass = emitter.mass.unwrap_or(radius * radius * PI);
+            let position = emitter.region.sample(&mut rgen, &bounds);
+            let velocity = emitter.velocity.sample(&mut rgen);
+
+            let mut particle = spawn_particle(
+                &mut commands,
+                &assets.texture,
+                Physics::new(position.extend(0.0), velocity.extend(0.0), mass, radius),
+                &emitter.species,
+            );
+            if let Some(material) = emitter.material {
+                particle.insert(material);
+            }
+            if let Some(lifetime) = emitter.lifetime {
+                particle.insert(Lifetime(lifetime));
+            }
+        }
+    }
+}
+
+fn drain_sinks(
+    mut commands: Commands,
+    sinks: Query<&Sink>,
+    bodies: Query<(Entity, &Physics, Option<&RigidBodyKind>)>,
+    bounds: Res<WorldBounds>,
+) {
+    if sinks.is_empty() {
+        return;
+    }
+    for (entity, physics, kind) in bodies.iter() {
+        if kind.copied().unwrap_or_default() != RigidBodyKind::Dynamic {
+            continue;
+        }
+        let position = physics.position.xy();
+        if sinks
+            .iter()
+            .any(|sink| sink.region.contains(position, &bounds))
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn expire(mut commands: Commands, mut bodies: Query<(Entity, &mut Lifetime)>, time: Res<Time>) {
+    for (entity, mut lifetime) in bodies.iter_mut() {
+        lifetime.0 -= time.delta_seconds();
+        if lifetime.0 <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn draw_regions(
+    mut gizmos: Gizmos,
+    emitters: Query<&Emitter>,
+    sinks: Query<&Sink>,
+    bounds: Res<WorldBounds>,
+) {
+    let regions = emitters
+        .iter()
+        .map(|emitter| (emitter.region, Color::GREEN))
+        .chain(sinks.iter().map(|sink| (sink.region, Color::RED)));
+    for (region, color) in regions {
+        match region {
+            Region::World => {
+                gizmos.rect_2d(Vec2::ZERO, 0.0, bounds.half_extents * 2.0, color);
+            }
+            Region::Rect { min, max } => {
+                gizmos.rect_2d((min + max) / 2.0, 0.0, max - min, color);
+            }
+            Region::Circle { center, radius } => {
+                gizmos.circle_2d(center, radius, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    // App running `systems` once per update, each update lasting `seconds`
+    fn app_with<M>(systems: impl IntoSystemConfigs<M>, seconds: f32) -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<WorldBounds>()
+            .insert_resource(ScenarioAssets {
+                scenario: Handle::default(),
+                texture: Handle::default(),
+            })
+            .add_systems(
+                Update,
+                (
+                    move |mut time: ResMut<Time>| time.advance_by(Duration::from_secs_f32(seconds)),
+                    systems,
+                )
+                    .chain(),
+            );
+        app
+    }
+
+    fn bodies(app: &mut App) -> usize {
+        app.world.query::<&Physics>().iter(&app.world).count()
+    }
+
+    fn emitter(rate: f32) -> Emitter {
+        Emitter {
+            species: "emitted".to_string(),
+            rate,
+            region: Region::Circle {
+                center: Vec2::ZERO,
+                radius: 5.0,
+            },
+            velocity: VelocityDistribution::Fixed(Vec2::X),
+            radius: RadiusDistribution::Fixed(1.0),
+            mass: None,
+            material: None,
+            lifetime: Some(2.0),
+            pending: 0.0,
+        }
+    }
+
+    // 20 bodies per second over 1/8 second steps is two and a half bodies a step
+    #[test]
+    fn emitters_spawn_their_rate_over_time() {
+        let mut app = app_with(emit, 0.125);
+        let spawner = app.world.spawn(emitter(20.0)).id();
+
+        app.update();
+        assert_eq!(bodies(&mut app), 2);
+        assert_eq!(app.world.get::<Emitter>(spawner).unwrap().pending, 0.5);
+        app.update();
+        assert_eq!(bodies(&mut app), 5);
+        assert_eq!(app.world.get::<Emitter>(spawner).unwrap().pending, 0.0);
+
+        let lifetimes: Vec<f32> = app
+            .world
+            .query::<&Lifetime>()
+            .iter(&app.world)
+            .map(|lifetime| lifetime.0)
+            .collect();
+        assert_eq!(lifetimes, [2.0; 5]);
+    }
+
+    // Half a body a step spawns one every other step, never a part of one early
+    #[test]
+    fn emitters_only_spawn_whole_bodies() {
+        let mut app = app_with(emit, 0.125);
+        app.world.spawn(emitter(4.0));
+
+        for expected in [0, 1, 1, 2, 2, 3] {
+            app.update();
+            assert_eq!(bodies(&mut app), expected);
+        }
+    }
+
+    #[test]
+    fn sinks_only_drain_dynamic_bodies() {
+        let mut app = app_with(drain_sinks, 0.125);
+        app.world.spawn(Sink {
+            region: Region::Rect {
+                min: Vec2::splat(-10.0),
+                max: Vec2::splat(10.0),
+            },
+        });
+        let mut spawn = |x: f32, kind: Option<RigidBodyKind>| {
+            let mut body =
+                app.world
+                    .spawn(Physics::new(Vec3::new(x, 0.0, 0.0), Vec3::ZERO, 1.0, 1.0));
+            if let Some(kind) = kind {
+                body.insert(kind);
+            }
+            body.id()
+        };
+        let inside = spawn(0.0, None);
+        let dynamic = spawn(1.0, Some(RigidBodyKind::Dynamic));
+        let fixed = spawn(2.0, Some(RigidBodyKind::Static));
+        let kinematic = spawn(3.0, Some(RigidBodyKind::Kinematic));
+        let outside = spawn(20.0, None);
+        app.update();
+
+        for (entity, kept) in [
+            (inside, false),
+            (dynamic, false),
+            (fixed, true),
+            (kinematic, true),
+            (outside, true),
+        ] {
+            assert_eq!(app.world.get_entity(entity).is_some(), kept);
+        }
+    }
+
+    #[test]
+    fn lifetimes_run_out() {
+        let mut app = app_with(expire, 0.125);
+        let body = app.world.spawn(Lifetime(0.3)).id();
+
+        app.update();
+        app.update();
+        let left = app.world.get::<Lifetime>(body).unwrap().0;
+        assert!((left - 0.05).abs() < 1e-6, "{} seconds left", left);
+        app.update();
+        assert!(app.world.get_entity(body).is_none());
+    }
+}
@@ -0,0 +1,227 @@
+use std::f32::consts::PI;
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use crate::physics::{query_around, separation, wrap_shifts, Body, Boundary};
+use crate::quad_trees::{Quadtree, WorldBounds};
+
+// Smoothed particle hydrodynamics: bodies push each other through the pressure of the
+// liquid they make up instead of colliding as hard spheres
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct FluidSettings {
+    // Neighbours further away than this don't affect a particle
+    pub smoothing_radius: f32,
+    // Mass per unit of area the liquid settles at
+    pub rest_density: f32,
+    // Pressure per unit of density above the rest density
+    pub stiffness: f32,
+    pub viscosity: f32,
+}
+
+// Radius 1 particles packed 2 units apart. Stiff enough to hold up under gravity,
+// which needs about 4 substeps at the default time step to stay stable.
+impl Default for FluidSettings {
+    fn default() -> Self {
+        Self {
+            smoothing_radius: 5.0,
+            rest_density: 0.8,
+            stiffness: 20000.0,
+            viscosity: 20.0,
+        }
+    }
+}
+
+// 2D kernels from Müller et al., "Particle-Based Fluid Simulation for Interactive Applications"
+pub fn poly6(distance_squared: f32, h: f32) -> f32 {
+    let left = h * h - distance_squared;
+    if left <= 0.0 {
+        return 0.0;
+    }
+    4.0 / (PI * h.powi(8)) * left * left * left
+}
+
+// Magnitude of the gradient, pointing away from the neighbour
+fn spiky_gradient(distance: f32, h: f32) -> f32 {
+    let left = (h - distance).max(0.0);
+    30.0 / (PI * h.powi(5)) * left * left
+}
+
+fn viscosity_laplacian(distance: f32, h: f32) -> f32 {
+    40.0 / (PI * h.powi(5)) * (h - distance).max(0.0)
+}
+
+// Adds the pressure and viscosity accelerations to the dynamic bodies
+pub fn apply_fluid(
+    bodies: &mut [Body],
+    quadtree: &Quadtree,
+    index: &HashMap<Entity, usize>,
+    fluid: &FluidSettings,
+    bounds: &WorldBounds,
+    boundary: Boundary,
+) {
+    let h = fluid.smoothing_radius;
+    let neighbours = Neighbours::find(bodies, quadtree, index, fluid, bounds, boundary);
+    let densities: Vec<f32> = (0..bodies.len())
+        .map(|i| neighbours.density(bodies, i, h))
+        .collect();
+    // Only pushing, pulling makes the particles clump together
+    let pressures: Vec<f32> = densities
+        .iter()
+        .map(|density| fluid.stiffness * (density - fluid.rest_density).max(0.0))
+        .collect();
+
+    for i in 0..bodies.len() {
+        if !bodies[i].is_dynamic() {
+            continue;
+        }
+        let mut force = Vec3::ZERO;
+        for &(j, offset) in neighbours.of(i) {
+            if i == j {
+                continue;
+            }
+            let distance = offset.length();
+            // Particles pressed into the same corner still have to be pushed apart somehow
+            let direction = if distance > f32::EPSILON {
+                offset / distance
+            } else if i < j {
+                Vec3::X
+            } else {
+                Vec3::NEG_X
+            };
+            let other = &bodies[j];
+            force -= direction
+                * other.mass
+                * (pressures[i] + pressures[j])
+                * spiky_gradient(distance, h)
+                / (2.0 * densities[j]);
+            force += (other.velocity - bodies[i].velocity)
+                * fluid.viscosity
+                * other.mass
+                * viscosity_laplacian(distance, h)
+                / densities[j];
+        }
+        bodies[i].acceleration += force / densities[i];
+    }
+}
+
+// Bodies within the smoothing radius of each body, with the offset to them
+struct Neighbours {
+    starts: Vec<usize>,
+    found: Vec<(usize, Vec3)>,
+}
+
+impl Neighbours {
+    fn find(
+        bodies: &[Body],
+        quadtree: &Quadtree,
+        index: &HashMap<Entity, usize>,
+        fluid: &FluidSettings,
+        bounds: &WorldBounds,
+        boundary: Boundary,
+    ) -> Self {
+        let h = fluid.smoothing_radius;
+        let shifts = wrap_shifts(bounds, boundary);
+        let mut starts = Vec::with_capacity(bodies.len() + 1);
+        let mut found = Vec::new();
+        let mut candidates = Vec::new();
+        for body in bodies.iter() {
+            starts.push(found.len());
+            candidates.clear();
+            query_around(quadtree, body.position, h, &shifts, bounds, &mut candidates);
+            for candidate in candidates.iter() {
+                let Some(&j) = index.get(candidate) else {
+                    continue;
+                };
+                let offset = separation(body.position, bodies[j].position, bounds, boundary);
+                if offset.length_squared() < h * h {
+                    found.push((j, offset));
+                }
+            }
+        }
+        starts.push(found.len());
+        Self { starts, found }
+    }
+
+    fn of(&self, i: usize) -> &[(usize, Vec3)] {
+        &self.found[self.starts[i]..self.starts[i + 1]]
+    }
+
+    fn density(&self, bodies: &[Body], i: usize, h: f32) -> f32 {
+        self.of(i)
+            .iter()
+            .map(|(j, offset)| bodies[*j].mass * poly6(offset.length_squared(), h))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gravity::{GravityField, GravitySettings};
+    use crate::physics::tests::{body, edge_walls};
+    use crate::physics::{step, PhysicsSettings};
+
+    // A column of liquid spreads over the floor of its box and comes to rest
+    // without particles flying off or piling up on each other
+    #[test]
+    fn liquid_settles_in_a_box() {
+        let settings = PhysicsSettings {
+            substeps: 4,
+            fluid: Some(FluidSettings::default()),
+            ..default()
+        };
+        let bounds = WorldBounds {
+            half_extents: Vec2::new(30.0, 30.0),
+        };
+        let gravity = GravityField {
+            settings: GravitySettings::Uniform(Vec2::new(0.0, -100.0)),
+            attractors: &[],
+        };
+        let mut bodies: Vec<Body> = (0..300)
+            .map(|i| {
+                let position = Vec2::new((i % 10) as f32, (i / 10) as f32) * 2.0 - 29.0;
+                body(i, position, Vec2::ZERO, 1.0)
+            })
+            .collect();
+
+        let walls = edge_walls(&bounds);
+        let mut quadtree = Quadtree::default();
+        for _ in 0..300 {
+            step(
+                &mut bodies,
+                &walls,
+                &[],
+                &gravity,
+                &mut quadtree,
+                &bounds,
+                &settings,
+            );
+        }
+
+        let max_speed = bodies
+            .iter()
+            .map(|body| body.velocity.length())
+            .fold(0.0, f32::max);
+        let top = bodies
+            .iter()
+            .map(|body| body.position.y)
+            .fold(f32::MIN, f32::max);
+        // Average distance to the nearest neighbour, 2 when packed at the rest density
+        let spacing = bodies
+            .iter()
+            .map(|a| {
+                bodies
+                    .iter()
+                    .filter(|b| b.entity != a.entity)
+                    .map(|b| a.position.distance(b.position))
+                    .fold(f32::MAX, f32::min)
+            })
+            .sum::<f32>()
+            / bodies.len() as f32;
+        // 300 particles at rest density fill about a fifth of the 60 unit wide box
+        assert!(max_speed < 20.0, "liquid still moving at {}", max_speed);
+        assert!(top < -10.0, "liquid didn't spread out, top at {}", top);
+        assert!(spacing > 1.0, "liquid squashed to {} spacing", spacing);
+    }
+}
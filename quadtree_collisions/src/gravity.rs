@@ -0,0 +1,336 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::physics::Body;
+use crate::quad_trees::Quadtree;
+
+pub struct GravityPlugin;
+impl Plugin for GravityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GravitySettings>()
+            .add_systems(Update, draw_attractors);
+    }
+}
+
+// Where the pull on the bodies comes from, can be changed while running
+#[derive(Resource, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum GravitySettings {
+    None,
+    // Same acceleration everywhere, (0, -100) makes a floor worth falling onto
+    Uniform(Vec2),
+    // Towards every Attractor entity
+    Attractors,
+    // Every body pulls on every other one, quadratic in the number of bodies.
+    // `softening` keeps close pairs from flinging each other away.
+    NBody { strength: f32, softening: f32 },
+    // Towards the quadtree leaves, weighted by how many particles each one holds
+    Leaves { strength: f32 },
+}
+
+impl Default for GravitySettings {
+    fn default() -> Self {
+        GravitySettings::Leaves { strength: 0.0002 }
+    }
+}
+
+// Point pulling bodies towards its Transform, or pushing them away with a negative strength
+#[derive(Component, Deserialize, Debug, Clone, Copy)]
+pub struct Attractor {
+    // Acceleration at 1 unit away
+    pub strength: f32,
+    // Power of the distance the pull drops with, 2 for inverse square
+    #[serde(default = "default_falloff")]
+    pub falloff: f32,
+}
+
+fn default_falloff() -> f32 {
+    2.0
+}
+
+impl Attractor {
+    pub fn at(&self, position: Vec3) -> PointAttractor {
+        PointAttractor {
+            position,
+            strength: self.strength,
+            falloff: self.falloff,
+        }
+    }
+}
+
+// Attractor as the solver sees it
+#[derive(Debug, Clone, Copy)]
+pub struct PointAttractor {
+    pub position: Vec3,
+    pub strength: f32,
+    pub falloff: f32,
+}
+
+impl PointAttractor {
+    // The pull stops growing inside 1 unit, so bodies can pass through the centre
+    fn acceleration(&self, at: Vec3) -> Vec3 {
+        let offset = self.position - at;
+        let distance = offset.length();
+        if distance <= f32::EPSILON {
+            return Vec3::ZERO;
+        }
+        offset / distance * self.strength / distance.max(1.0).powf(self.falloff)
+    }
+
+    // Per unit of mass, zero at 1 unit away for the logarithmic falloff
+    fn potential(&self, at: Vec3) -> f32 {
+        let distance = self.position.distance(at);
+        let at_one = if self.falloff == 1.0 {
+            0.0
+        } else {
+            self.strength / (1.0 - self.falloff)
+        };
+        if distance < 1.0 {
+            at_one - self.strength * (1.0 - distance)
+        } else if self.falloff == 1.0 {
+            self.strength * distance.ln()
+        } else {
+            self.strength * distance.powf(1.0 - self.falloff) / (1.0 - self.falloff)
+        }
+    }
+}
+
+// Gravity as the step sees it
+#[derive(Debug, Clone, Copy)]
+pub struct GravityField<'a> {
+    pub settings: GravitySettings,
+    pub attractors: &'a [PointAttractor],
+}
+
+impl GravityField<'_> {
+    pub const NONE: GravityField<'static> = GravityField {
+        settings: GravitySettings::None,
+        attractors: &[],
+    };
+}
+
+pub fn apply_gravity(bodies: &mut [Body], quadtree: &Quadtree, field: &GravityField) {
+    match field.settings {
+        GravitySettings::None => {
+            for body in bodies.iter_mut() {
+                body.acceleration = Vec3::ZERO;
+            }
+        }
+        GravitySettings::Uniform(acceleration) => {
+            for body in bodies.iter_mut() {
+                body.acceleration = acceleration.extend(0.0);
+            }
+        }
+        GravitySettings::Attractors => {
+            for body in bodies.iter_mut() {
+                body.acceleration = field
+                    .attractors
+                    .iter()
+                    .map(|attractor| attractor.acceleration(body.position))
+                    .sum();
+            }
+        }
+        GravitySettings::NBody {
+            strength,
+            softening,
+        } => apply_n_body(bodies, strength, softening),
+        GravitySettings::Leaves { strength } => apply_leaves(bodies, quadtree, strength),
+    }
+}
+
+fn apply_n_body(bodies: &mut [Body], strength: f32, softening: f32) {
+    let sources: Vec<(Vec3, f32)> = bodies
+        .iter()
+        .map(|body| (body.position, body.mass))
+        .collect();
+
+    for (i, body) in bodies.iter_mut().enumerate() {
+        let mut acceleration = Vec3::ZERO;
+        for (j, (position, mass)) in sources.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            let offset = *position - body.position;
+            let distance_squared = offset.length_squared() + softening * softening;
+            acceleration +=
+                offset * (strength * mass / (distance_squared * distance_squared.sqrt()));
+        }
+        body.acceleration = acceleration;
+    }
+}
+
+fn apply_leaves(bodies: &mut [Body], quadtree: &Quadtree, strength: f32) {
+    let mut huntsman: Vec<(f32, Vec3)> = Vec::new();
+    quadtree.huntsman(&mut huntsman);
+
+    for body in bodies.iter_mut() {
+        let mut n_acc = Vec3::ZERO;
+        for (n, pos) in huntsman.iter() {
+            let distance = body.position.distance_squared(*pos).min(1.0);
+            let normal = (body.position - *pos).normalize_or_zero();
+
+            n_acc += normal * (1.0 / distance) * *n;
+        }
+        body.acceleration = n_acc * -strength;
+    }
+}
+
+// Potential energy of the dynamic bodies in the field apply_gravity pulls them with
+pub fn gravity_potential(bodies: &[Body], quadtree: &Quadtree, field: &GravityField) -> f32 {
+    let dynamic = bodies.iter().filter(|body| body.is_dynamic());
+    match field.settings {
+        GravitySettings::None => 0.0,
+        GravitySettings::Uniform(acceleration) => dynamic
+            .map(|body| -body.mass * acceleration.dot(body.position.xy()))
+            .sum(),
+        GravitySettings::Attractors => dynamic
+            .map(|body| {
+                field
+                    .attractors
+                    .iter()
+                    .map(|attractor| body.mass * attractor.potential(body.position))
+                    .sum::<f32>()
+            })
+            .sum(),
+        GravitySettings::NBody {
+            strength,
+            softening,
+        } => {
+            let mut energy = 0.0;
+            for (i, body1) in bodies.iter().enumerate() {
+                for body2 in bodies[i + 1..].iter() {
+                    if !body1.is_dynamic() && !body2.is_dynamic() {
+                        continue;
+                    }
+                    let distance_squared =
+                        body1.position.distance_squared(body2.position) + softening * softening;
+                    energy -= strength * body1.mass * body2.mass / distance_squared.sqrt();
+                }
+            }
+            energy
+        }
+        GravitySettings::Leaves { strength } => leaves_potential(bodies, quadtree, strength),
+    }
+}
+
+// The pull is constant beyond 1 unit from a leaf and inverse square inside it
+fn leaves_potential(bodies: &[Body], quadtree: &Quadtree, strength: f32) -> f32 {
+    let mut huntsman: Vec<(f32, Vec3)> = Vec::new();
+    quadtree.huntsman(&mut huntsman);
+
+    let mut energy = 0.0;
+    for body in bodies.iter().filter(|body| body.is_dynamic()) {
+        for (n, pos) in huntsman.iter() {
+            let distance = body.position.distance(*pos).max(f32::EPSILON);
+            let potential = if distance < 1.0 {
+                -1.0 / distance
+            } else {
+                distance - 2.0
+            };
+            energy += strength * *n * body.mass * potential;
+        }
+    }
+    energy
+}
+
+fn draw_attractors(
+    mut gizmos: Gizmos,
+    settings: Res<GravitySettings>,
+    attractors: Query<(&Attractor, &Transform)>,
+) {
+    if *settings != GravitySettings::Attractors {
+        return;
+    }
+    for (attractor, transform) in attractors.iter() {
+        let color = if attractor.strength >= 0.0 {
+            Color::YELLOW
+        } else {
+            Color::CYAN
+        };
+        gizmos.circle_2d(transform.translation.xy(), 6.0, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{measure, StepStats};
+    use crate::physics::tests::{body, bounds};
+    use crate::physics::{step, Integrator, PhysicsSettings};
+
+    // Runs `steps` steps in `field`, returning how much the total energy drifted
+    // compared to the energy moved between kinetic and potential
+    fn run_in(bodies: &mut [Body], field: &GravityField, steps: usize) -> f32 {
+        let settings = PhysicsSettings {
+            sleeping: false,
+            integrator: Integrator::VelocityVerlet,
+            ..default()
+        };
+        let bounds = bounds(1000.0);
+        let mut quadtree = Quadtree::default();
+        let start = measure(bodies, &[], &quadtree, field);
+        for _ in 0..steps {
+            step(bodies, &[], &[], field, &mut quadtree, &bounds, &settings);
+        }
+        let end = measure(bodies, &[], &quadtree, field);
+        let total = |stats: &StepStats| stats.kinetic_energy + stats.potential_energy;
+        let exchanged = (end.kinetic_energy - start.kinetic_energy).abs();
+        (total(&end) - total(&start)).abs() / exchanged
+    }
+
+    // Falling freely for one second
+    #[test]
+    fn uniform_gravity_falls_like_a_stone() {
+        let field = GravityField {
+            settings: GravitySettings::Uniform(Vec2::new(0.0, -100.0)),
+            attractors: &[],
+        };
+        let time_step = PhysicsSettings::default().time_step;
+        let steps = (1.0 / time_step).round() as usize;
+        let mut falling = [body(0, Vec2::ZERO, Vec2::ZERO, 1.0)];
+        let drift = run_in(&mut falling, &field, steps);
+
+        let time = steps as f32 * time_step;
+        assert!((falling[0].velocity.y + 100.0 * time).abs() < 1e-2);
+        assert!((falling[0].position.y + 50.0 * time * time).abs() < 1e-1);
+        assert!(drift < 1e-3, "uniform energy drift {:.2e}", drift);
+    }
+
+    // Dropped towards a point attractor from rest
+    #[test]
+    fn attractors_pull_towards_themselves() {
+        let attractors = [Attractor {
+            strength: 4000.0,
+            falloff: 2.0,
+        }
+        .at(Vec3::ZERO)];
+        let field = GravityField {
+            settings: GravitySettings::Attractors,
+            attractors: &attractors,
+        };
+        let mut dropped = [body(0, Vec2::new(100.0, 0.0), Vec2::ZERO, 1.0)];
+        let drift = run_in(&mut dropped, &field, 20);
+        assert!(dropped[0].velocity.x < 0.0 && dropped[0].velocity.y.abs() < 1e-5);
+        assert!(drift < 1e-2, "attractor energy drift {:.2e}", drift);
+    }
+
+    // Two bodies pulling each other keep their total momentum
+    #[test]
+    fn n_body_pairs_pull_each_other() {
+        let field = GravityField {
+            settings: GravitySettings::NBody {
+                strength: 10.0,
+                softening: 1.0,
+            },
+            attractors: &[],
+        };
+        let mut pair = [
+            body(0, Vec2::new(-20.0, 0.0), Vec2::new(0.0, 5.0), 2.0),
+            body(1, Vec2::new(20.0, 0.0), Vec2::new(0.0, -5.0), 2.0),
+        ];
+        let drift = run_in(&mut pair, &field, 50);
+        let momentum = pair[0].velocity * pair[0].mass + pair[1].velocity * pair[1].mass;
+        assert!(momentum.length() < 1e-3);
+        assert!(pair[0].position.x > -20.0 && pair[1].position.x < 20.0);
+        assert!(drift < 1e-2, "n-body energy drift {:.2e}", drift);
+    }
+}
@@ -0,0 +1,365 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::physics::{separation, Body, Boundary, Physics};
+use crate::quad_trees::WorldBounds;
+
+pub struct JointPlugin;
+impl Plugin for JointPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_joints);
+    }
+}
+
+// Ties a body to another one or to the world. Lives on its own entity, so a body
+// can have any number of them.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Joint {
+    pub body: Entity,
+    pub kind: JointKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum JointKind {
+    // Keeps the two bodies exactly `length` apart
+    Distance {
+        other: Entity,
+        length: f32,
+    },
+    // Pulls them towards `rest_length` apart, damping how fast they move along the spring
+    Spring {
+        other: Entity,
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    },
+    // Keeps them at most `max_length` apart
+    Rope {
+        other: Entity,
+        max_length: f32,
+    },
+    // Holds the body at a point of the world
+    Pin {
+        anchor: Vec2,
+    },
+}
+
+impl JointKind {
+    pub fn other(&self) -> Option<Entity> {
+        match *self {
+            JointKind::Distance { other, .. }
+            | JointKind::Spring { other, .. }
+            | JointKind::Rope { other, .. } => Some(other),
+            JointKind::Pin { .. } => None,
+        }
+    }
+}
+
+// A joint with its bodies looked up in the step
+#[derive(Debug, Clone, Copy)]
+pub struct Link {
+    pub body: usize,
+    pub other: Option<usize>,
+    pub kind: JointKind,
+}
+
+impl Link {
+    pub fn pair(&self) -> Option<[usize; 2]> {
+        self.other.map(|other| [self.body, other])
+    }
+}
+
+// Joints whose bodies are all part of the step
+pub fn resolve_joints(joints: &[Joint], index: &HashMap<Entity, usize>) -> Vec<Link> {
+    joints
+        .iter()
+        .filter_map(|joint| {
+            let body = *index.get(&joint.body)?;
+            let other = match joint.kind.other() {
+                Some(other) => Some(*index.get(&other).filter(|&&other| other != body)?),
+                None => None,
+            };
+            Some(Link {
+                body,
+                other,
+                kind: joint.kind,
+            })
+        })
+        .collect()
+}
+
+fn pair_mut(bodies: &mut [Body], i: usize, j: usize) -> (&mut Body, &mut Body) {
+    if i < j {
+        let (head, tail) = bodies.split_at_mut(j);
+        (&mut head[i], &mut tail[0])
+    } else {
+        let (head, tail) = bodies.split_at_mut(i);
+        (&mut tail[0], &mut head[j])
+    }
+}
+
+// Spring forces, applied once per substep as an impulse
+pub fn apply_springs(
+    bodies: &mut [Body],
+    links: &[Link],
+    dt: f32,
+    bounds: &WorldBounds,
+    boundary: Boundary,
+) {
+    for link in links.iter() {
+        let (
+            Some(other),
+            JointKind::Spring {
+                rest_length,
+                stiffness,
+                damping,
+                ..
+            },
+        ) = (link.other, link.kind)
+        else {
+            continue;
+        };
+        let (body1, body2) = pair_mut(bodies, link.body, other);
+
+        let offset = separation(body1.position, body2.position, bounds, boundary);
+        let distance = offset.length();
+        if distance <= f32::EPSILON {
+            continue;
+        }
+        let normal = offset / distance;
+
+        let stretch = distance - rest_length;
+        let speed = (body2.velocity - body1.velocity).dot(normal);
+        let impulse = normal * (stiffness * stretch + damping * speed) * dt;
+        body1.velocity += impulse * body1.inverse_mass();
+        body2.velocity -= impulse * body2.inverse_mass();
+    }
+}
+
+// One solver pass over the rigid joints, run next to the contacts in every iteration
+pub fn solve_joints(bodies: &mut [Body], links: &[Link], bounds: &WorldBounds, boundary: Boundary) {
+    for link in links.iter() {
+        match (link.other, link.kind) {
+            (Some(other), JointKind::Distance { length, .. }) => {
+                let (body1, body2) = pair_mut(bodies, link.body, other);
+                solve_length(body1, body2, length, false, bounds, boundary);
+            }
+            (Some(other), JointKind::Rope { max_length, .. }) => {
+                let (body1, body2) = pair_mut(bodies, link.body, other);
+                solve_length(body1, body2, max_length, true, bounds, boundary);
+            }
+            (_, JointKind::Pin { anchor }) => {
+                let body = &mut bodies[link.body];
+                if body.is_dynamic() {
+                    body.position = anchor.extend(0.0);
+                    body.velocity = Vec3::ZERO;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Moves two bodies back to `length` apart and cancels their speed along the joint.
+// A slack one only stops them from getting further apart.
+fn solve_length(
+    body1: &mut Body,
+    body2: &mut Body,
+    length: f32,
+    slack: bool,
+    bounds: &WorldBounds,
+    boundary: Boundary,
+) {
+    let total_inverse_mass = body1.inverse_mass() + body2.inverse_mass();
+    let offset = separation(body1.position, body2.position, bounds, boundary);
+    let distance = offset.length();
+    if total_inverse_mass == 0.0 || distance <= f32::EPSILON {
+        return;
+    }
+    let normal = offset / distance;
+
+    let error = distance - length;
+    if slack && error <= 0.0 {
+        return;
+    }
+    body1.position += normal * (error * body1.inverse_mass() / total_inverse_mass);
+    body2.position -= normal * (error * body2.inverse_mass() / total_inverse_mass);
+
+    let speed = (body2.velocity - body1.velocity).dot(normal);
+    if slack && speed <= 0.0 {
+        return;
+    }
+    let impulse = normal * (speed / total_inverse_mass);
+    body1.velocity += impulse * body1.inverse_mass();
+    body2.velocity -= impulse * body2.inverse_mass();
+}
+
+fn draw_joints(
+    mut gizmos: Gizmos,
+    joints: Query<&Joint>,
+    bodies: Query<&Transform, With<Physics>>,
+) {
+    for joint in joints.iter() {
+        let Ok(transform) = bodies.get(joint.body) else {
+            continue;
+        };
+        let start = transform.translation.xy();
+        let end = match joint.kind.other() {
+            Some(other) => match bodies.get(other) {
+                Ok(other) => other.translation.xy(),
+                Err(_) => continue,
+            },
+            None => match joint.kind {
+                JointKind::Pin { anchor } => anchor,
+                _ => continue,
+            },
+        };
+        gizmos.line_2d(start, end, Color::GRAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gravity::{GravityField, GravitySettings};
+    use crate::physics::tests::{body, bounds};
+    use crate::physics::{step, PhysicsSettings};
+    use crate::quad_trees::Quadtree;
+
+    // Runs `steps` steps of the joints alone, without collisions or sleep
+    fn run_joints(bodies: &mut [Body], joints: &[Joint], gravity: &GravityField, steps: usize) {
+        let settings = PhysicsSettings {
+            collisions: false,
+            sleeping: false,
+            ..default()
+        };
+        let bounds = bounds(1000.0);
+        let mut quadtree = Quadtree::default();
+        for _ in 0..steps {
+            step(
+                bodies,
+                &[],
+                joints,
+                gravity,
+                &mut quadtree,
+                &bounds,
+                &settings,
+            );
+        }
+    }
+
+    fn distance(bodies: &[Body], i: usize, j: usize) -> f32 {
+        bodies[i].position.distance(bodies[j].position)
+    }
+
+    // A chain laid out sideways and pinned by its first link swings down and hangs
+    // straight under it, with every link still the same length
+    #[test]
+    fn pinned_chain_hangs_from_its_first_link() {
+        let gravity = GravityField {
+            settings: GravitySettings::Uniform(Vec2::new(0.0, -100.0)),
+            attractors: &[],
+        };
+        let mut bodies: Vec<Body> = (0..5)
+            .map(|i| body(i, Vec2::new(2.0 * i as f32, 0.0), Vec2::ZERO, 0.5))
+            .collect();
+        let mut joints: Vec<Joint> = (0..4)
+            .map(|i| Joint {
+                body: bodies[i].entity,
+                kind: JointKind::Distance {
+                    other: bodies[i + 1].entity,
+                    length: 2.0,
+                },
+            })
+            .collect();
+        joints.push(Joint {
+            body: bodies[0].entity,
+            kind: JointKind::Pin { anchor: Vec2::ZERO },
+        });
+
+        run_joints(&mut bodies, &joints, &gravity, 300);
+        // Gravity still moves it for one step after the pin put it back
+        assert!(bodies[0].position.length() < 0.1);
+        for i in 0..4 {
+            let length = distance(&bodies, i, i + 1);
+            assert!((length - 2.0).abs() < 0.05, "link {} is {} long", i, length);
+        }
+        let end = bodies[4].position;
+        assert!(end.y < -7.5, "chain end still at {:?}", end);
+        assert!(end.x.abs() < 1.0, "chain end still at {:?}", end);
+    }
+
+    // Two bodies flying apart or towards each other while turning stay the joint length apart
+    #[test]
+    fn distance_joints_hold_their_length() {
+        for speed in [50.0, -50.0] {
+            let mut bodies = [
+                body(0, Vec2::new(-2.5, 0.0), Vec2::new(-speed, 10.0), 0.5),
+                body(1, Vec2::new(2.5, 0.0), Vec2::new(speed, 0.0), 0.5),
+            ];
+            let joints = [Joint {
+                body: bodies[0].entity,
+                kind: JointKind::Distance {
+                    other: bodies[1].entity,
+                    length: 5.0,
+                },
+            }];
+            run_joints(&mut bodies, &joints, &GravityField::NONE, 20);
+            // Turning around each other moves them a little off between solver passes
+            assert!((distance(&bodies, 0, 1) - 5.0).abs() < 0.02);
+        }
+    }
+
+    // A rope lets its ends come closer without pushing them apart,
+    // but stops them at its length when they fly apart
+    #[test]
+    fn ropes_only_stop_stretching() {
+        let rope = |bodies: &[Body]| {
+            [Joint {
+                body: bodies[0].entity,
+                kind: JointKind::Rope {
+                    other: bodies[1].entity,
+                    max_length: 5.0,
+                },
+            }]
+        };
+
+        let mut slack = [
+            body(0, Vec2::new(-1.0, 0.0), Vec2::ZERO, 0.5),
+            body(1, Vec2::new(1.0, 0.0), Vec2::ZERO, 0.5),
+        ];
+        let joints = rope(&slack);
+        run_joints(&mut slack, &joints, &GravityField::NONE, 20);
+        assert_eq!(distance(&slack, 0, 1), 2.0);
+
+        let mut taut = [
+            body(0, Vec2::new(-1.0, 0.0), Vec2::new(-50.0, 0.0), 0.5),
+            body(1, Vec2::new(1.0, 0.0), Vec2::new(50.0, 0.0), 0.5),
+        ];
+        let joints = rope(&taut);
+        run_joints(&mut taut, &joints, &GravityField::NONE, 20);
+        assert!((distance(&taut, 0, 1) - 5.0).abs() < 1e-3);
+        // Nothing pulls them back together once it is tight
+        assert!(taut[1].velocity.x - taut[0].velocity.x >= 0.0);
+    }
+
+    // A stretched damped spring pulls its ends in until they rest at its rest length
+    #[test]
+    fn springs_settle_at_their_rest_length() {
+        let mut bodies = [
+            body(0, Vec2::new(-5.0, 0.0), Vec2::ZERO, 0.5),
+            body(1, Vec2::new(5.0, 0.0), Vec2::ZERO, 0.5),
+        ];
+        let joints = [Joint {
+            body: bodies[0].entity,
+            kind: JointKind::Spring {
+                other: bodies[1].entity,
+                rest_length: 4.0,
+                stiffness: 200.0,
+                damping: 20.0,
+            },
+        }];
+        run_joints(&mut bodies, &joints, &GravityField::NONE, 300);
+        assert!((distance(&bodies, 0, 1) - 4.0).abs() < 1e-2);
+        assert!(bodies[0].velocity.length() < 1e-2 && bodies[1].velocity.length() < 1e-2);
+    }
+}
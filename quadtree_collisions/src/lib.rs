@@ -1,2 +1,17 @@
-mod physics;
-mod quad_trees;
+pub mod collider;
+pub mod contacts;
+pub mod diagnostics;
+pub mod emitter;
+pub mod fluid;
+pub mod gravity;
+pub mod joints;
+pub mod material;
+pub mod panel;
+pub mod physics;
+pub mod picking;
+pub mod quad_trees;
+pub mod replay;
+pub mod scenario;
+pub mod sensor;
+pub mod sleep;
+pub mod visualisation;
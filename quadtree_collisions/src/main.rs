@@ -1,69 +1,44 @@
-mod physics;
-mod quad_trees;
-
-use crate::physics::{Physics, PhysicsPlugin};
-use bevy::{
-    prelude::*,
-    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
-};
-
-use rand::{thread_rng, Rng};
-use std::f32::consts::PI;
-
-use quad_trees::{QuadTreeDetect, QuadtreePlugin, X_EXTENT, Y_EXTENT};
+use bevy::{diagnostic::LogDiagnosticsPlugin, prelude::*};
+
+use quadtree_collisions::contacts::ContactPlugin;
+use quadtree_collisions::diagnostics::PhysicsDiagnosticsPlugin;
+use quadtree_collisions::emitter::EmitterPlugin;
+use quadtree_collisions::gravity::GravityPlugin;
+use quadtree_collisions::joints::JointPlugin;
+use quadtree_collisions::panel::PanelPlugin;
+use quadtree_collisions::physics::PhysicsPlugin;
+use quadtree_collisions::picking::PickingPlugin;
+use quadtree_collisions::quad_trees::QuadtreePlugin;
+use quadtree_collisions::replay::ReplayPlugin;
+use quadtree_collisions::scenario::ScenarioPlugin;
+use quadtree_collisions::sensor::SensorPlugin;
+use quadtree_collisions::visualisation::VisualisationPlugin;
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(QuadtreePlugin)
         .add_plugins(PhysicsPlugin)
-        .add_systems(Startup, (spawn_particles, spawn_camera))
+        .add_plugins(GravityPlugin)
+        .add_plugins(ContactPlugin)
+        .add_plugins(JointPlugin)
+        .add_plugins(SensorPlugin)
+        .add_plugins(EmitterPlugin)
+        .add_plugins(PhysicsDiagnosticsPlugin)
+        .add_plugins(LogDiagnosticsPlugin {
+            filter: Some(PhysicsDiagnosticsPlugin::ALL.to_vec()),
+            ..default()
+        })
+        .add_plugins(ScenarioPlugin)
+        .add_plugins(ReplayPlugin)
+        .add_plugins(PanelPlugin)
+        .add_plugins(PickingPlugin)
+        .add_plugins(VisualisationPlugin)
+        .add_systems(Startup, spawn_camera)
         .add_systems(Update, print_fps)
         .run()
 }
 
-const MAX_SPEED: f32 = 500f32;
-const RADIUS: f32 = 1f32;
-const N_ENTITIES: usize = 10_000;
-
-fn spawn_particles(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    let mut rgen = thread_rng();
-
-    let mesh_hande = Mesh2dHandle(meshes.add(Circle::new(RADIUS)));
-    let color = Color::WHITE;
-
-    for _ in 0..N_ENTITIES {
-        // Distribute colors evenly across the rainbow.
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: mesh_hande.clone(),
-                material: materials.add(color),
-                transform: Transform::from_xyz(
-                    rgen.gen_range(-X_EXTENT..X_EXTENT),
-                    rgen.gen_range(-Y_EXTENT..Y_EXTENT),
-                    0.0,
-                ),
-                ..default()
-            },
-            Physics {
-                mass: RADIUS * RADIUS * PI,
-                collider_radius: RADIUS,
-                velocity: Vec3::new(
-                    rgen.gen_range(-MAX_SPEED..MAX_SPEED),
-                    rgen.gen_range(-MAX_SPEED..MAX_SPEED),
-                    0.0,
-                ),
-                acceleration: Vec3::new(0.0, 0.0, 0.0),
-            },
-            QuadTreeDetect,
-        ));
-    }
-}
-
 fn spawn_camera(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
 }
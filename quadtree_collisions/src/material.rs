@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+// How the values of two touching bodies are merged. When the two rules differ
+// the one declared last wins, so Max beats Multiply beats Min beats Average.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CombineRule {
+    #[default]
+    Average,
+    Min,
+    Multiply,
+    Max,
+}
+
+impl CombineRule {
+    pub fn combine(self, a: f32, b: f32) -> f32 {
+        match self {
+            CombineRule::Average => (a + b) / 2.0,
+            CombineRule::Min => a.min(b),
+            CombineRule::Multiply => a * b,
+            CombineRule::Max => a.max(b),
+        }
+    }
+}
+
+// Surface of a body. Bodies without one use the restitution from PhysicsSettings and no friction.
+#[derive(Component, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsMaterial {
+    pub restitution: f32,
+    // Friction coefficient below which touching bodies stick together
+    pub static_friction: f32,
+    // Friction coefficient while sliding
+    pub dynamic_friction: f32,
+    #[serde(default)]
+    pub combine: CombineRule,
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self {
+            restitution: 0.5,
+            static_friction: 0.0,
+            dynamic_friction: 0.0,
+            combine: CombineRule::Average,
+        }
+    }
+}
+
+impl PhysicsMaterial {
+    // Restitution, static friction and dynamic friction of a contact between the two materials
+    pub fn mix(&self, other: &PhysicsMaterial) -> (f32, f32, f32) {
+        let rule = self.combine.max(other.combine);
+        (
+            rule.combine(self.restitution, other.restitution),
+            rule.combine(self.static_friction, other.static_friction),
+            rule.combine(self.dynamic_friction, other.dynamic_friction),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collider::ColliderShape;
+    use crate::gravity::{GravityField, GravitySettings};
+    use crate::physics::tests::{body, bounds, shaped};
+    use crate::physics::{step, Body, PhysicsSettings, RigidBodyKind};
+    use crate::quad_trees::Quadtree;
+
+    // A puck that can't spin, pushed along a static floor made of `material`,
+    // returning it after `steps` steps
+    fn slide(material: PhysicsMaterial, speed: f32, steps: usize) -> Body {
+        let settings = PhysicsSettings {
+            sleeping: false,
+            ..default()
+        };
+        let gravity = GravityField {
+            settings: GravitySettings::Uniform(Vec2::new(0.0, -100.0)),
+            attractors: &[],
+        };
+        let floor = ColliderShape::Aabb {
+            half_extents: Vec2::new(100.0, 1.0),
+        };
+        let mut bodies = [
+            Body {
+                kind: RigidBodyKind::Static,
+                material,
+                ..shaped(0, Vec2::ZERO, &floor)
+            },
+            Body {
+                inertia: f32::INFINITY,
+                material,
+                ..body(1, Vec2::new(0.0, 2.0), Vec2::new(speed, 0.0), 1.0)
+            },
+        ];
+
+        let bounds = bounds(200.0);
+        let mut quadtree = Quadtree::default();
+        for _ in 0..steps {
+            step(
+                &mut bodies,
+                &[],
+                &[],
+                &gravity,
+                &mut quadtree,
+                &bounds,
+                &settings,
+            );
+        }
+        bodies[1]
+    }
+
+    fn rough(static_friction: f32, dynamic_friction: f32) -> PhysicsMaterial {
+        PhysicsMaterial {
+            restitution: 0.0,
+            static_friction,
+            dynamic_friction,
+            combine: CombineRule::Average,
+        }
+    }
+
+    // A slow push is fully taken by static friction, the puck sticks where it is
+    #[test]
+    fn static_friction_stops_sliding() {
+        let puck = slide(rough(0.8, 0.5), 1.0, 2);
+        assert!(
+            puck.velocity.x.abs() < 1e-4,
+            "still sliding at {}",
+            puck.velocity.x
+        );
+        let later = slide(rough(0.8, 0.5), 1.0, 20);
+        assert!((later.position.x - puck.position.x).abs() < 1e-4);
+    }
+
+    // Sliding on a floor slows down by the dynamic friction times the weight,
+    // 0.5 * 100 = 50 units per second every second
+    #[test]
+    fn dynamic_friction_slows_by_its_share_of_the_weight() {
+        let time_step = PhysicsSettings::default().time_step;
+        let steps = 10;
+        let puck = slide(rough(0.5, 0.5), 40.0, steps);
+        // Gravity is applied after the contacts, so the first step only presses it on the floor
+        let expected = 40.0 - 50.0 * (steps - 1) as f32 * time_step;
+        assert!(
+            (puck.velocity.x - expected).abs() < 0.1,
+            "sliding at {} instead of {}",
+            puck.velocity.x,
+            expected
+        );
+
+        // Without friction nothing slows it down
+        let free = slide(rough(0.0, 0.0), 40.0, steps);
+        assert!((free.velocity.x - 40.0).abs() < 1e-4);
+    }
+
+    // The rule declared last wins when two materials disagree
+    #[test]
+    fn combine_rules_pick_the_later_rule() {
+        let material = |restitution, combine| PhysicsMaterial {
+            restitution,
+            static_friction: restitution,
+            dynamic_friction: restitution,
+            combine,
+        };
+        let mix = |a: CombineRule, b: CombineRule| material(0.2, a).mix(&material(0.6, b)).0;
+
+        assert!((mix(CombineRule::Average, CombineRule::Average) - 0.4).abs() < 1e-6);
+        assert_eq!(mix(CombineRule::Average, CombineRule::Min), 0.2);
+        assert!((mix(CombineRule::Multiply, CombineRule::Min) - 0.12).abs() < 1e-6);
+        assert_eq!(mix(CombineRule::Multiply, CombineRule::Max), 0.6);
+        // Either way round
+        assert_eq!(
+            material(0.2, CombineRule::Max).mix(&material(0.6, CombineRule::Min)),
+            material(0.6, CombineRule::Min).mix(&material(0.2, CombineRule::Max)),
+        );
+    }
+}
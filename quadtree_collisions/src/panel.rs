@@ -0,0 +1,122 @@
+use bevy::{app::FixedMain, prelude::*};
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+
+use crate::gravity::GravitySettings;
+use crate::physics::PhysicsSettings;
+use crate::replay::SimMode;
+
+pub struct PanelPlugin;
+impl Plugin for PanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(EguiPlugin)
+            .init_resource::<StepRequest>()
+            .add_systems(
+                Update,
+                (
+                    settings_panel,
+                    step_once.run_if(resource_equals(StepRequest(true))),
+                )
+                    .chain()
+                    .run_if(in_state(SimMode::Live)),
+            )
+            .add_systems(OnEnter(SimMode::Replay), resume);
+    }
+}
+
+// Set by the Step button, runs one fixed step while the simulation is paused
+#[derive(Resource, Default, PartialEq)]
+struct StepRequest(bool);
+
+fn settings_panel(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<PhysicsSettings>,
+    mut gravity: ResMut<GravitySettings>,
+    mut time: ResMut<Time<Virtual>>,
+    mut step: ResMut<StepRequest>,
+) {
+    egui::Window::new("Physics").show(contexts.ctx_mut(), |ui| {
+        let mut paused = time.is_paused();
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut paused, "Paused");
+            if ui.add_enabled(paused, egui::Button::new("Step")).clicked() {
+                step.0 = true;
+            }
+        });
+        if paused != time.is_paused() {
+            if paused {
+                time.pause();
+            } else {
+                time.unpause();
+            }
+        }
+
+        // Only written back when something changed, so change detection stays useful
+        let mut edited = *settings;
+        ui.checkbox(&mut edited.collisions, "Collisions");
+        ui.checkbox(&mut edited.gravity, "Gravity");
+        ui.add(egui::Slider::new(&mut edited.iterations, 1..=64).text("Iterations"));
+        ui.add(egui::Slider::new(&mut edited.substeps, 1..=16).text("Substeps"));
+        ui.add(
+            egui::Slider::new(&mut edited.time_step, 0.001..=0.1)
+                .logarithmic(true)
+                .text("Time step"),
+        );
+        ui.add(egui::Slider::new(&mut edited.restitution, 0.0..=1.0).text("Restitution"));
+        if edited != *settings {
+            *settings = edited;
+        }
+
+        let mut edited = *gravity;
+        match &mut edited {
+            GravitySettings::None => {
+                ui.label("No gravity");
+            }
+            GravitySettings::Uniform(acceleration) => {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut acceleration.x).prefix("x: "));
+                    ui.add(egui::DragValue::new(&mut acceleration.y).prefix("y: "));
+                    ui.label("Gravity");
+                });
+            }
+            GravitySettings::Attractors => {
+                ui.label("Gravity from attractors");
+            }
+            GravitySettings::NBody {
+                strength,
+                softening,
+            } => {
+                ui.add(
+                    egui::Slider::new(strength, 0.0..=1000.0)
+                        .logarithmic(true)
+                        .text("Gravity strength"),
+                );
+                ui.add(egui::Slider::new(softening, 0.0..=20.0).text("Softening"));
+            }
+            GravitySettings::Leaves { strength } => {
+                ui.add(
+                    egui::Slider::new(strength, 0.0..=0.01)
+                        .logarithmic(true)
+                        .text("Gravity strength"),
+                );
+            }
+        }
+        if edited != *gravity {
+            *gravity = edited;
+        }
+    });
+}
+
+// FixedUpdate doesn't run while virtual time is paused, so the step is run by hand
+fn step_once(world: &mut World) {
+    world.resource_mut::<StepRequest>().0 = false;
+    let time_step = world.resource::<Time<Fixed>>().timestep();
+    world.resource_mut::<Time<Fixed>>().advance_by(time_step);
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+    world.run_schedule(FixedMain);
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
+
+// Replays play back in virtual time, and have their own pause
+fn resume(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
@@ -1,144 +1,1582 @@
-use bevy::{a11y::accesskit::Tree, prelude::*};
+use bevy::{ecs::system::SystemParam, prelude::*, transform::TransformSystem, utils::HashMap};
+use serde::Deserialize;
 use std::collections::HashSet;
 
-use crate::quad_trees::{QuadTreeDetect, Quadtree, X_EXTENT, Y_EXTENT};
+use crate::collider::{circle_inertia, collide, draw_colliders, ColliderShape, Polygon, Shape};
+use crate::contacts::{Contact, StepContacts};
+use crate::fluid::{apply_fluid, FluidSettings};
+use crate::gravity::{apply_gravity, Attractor, GravityField, GravitySettings, PointAttractor};
+use crate::joints::{apply_springs, resolve_joints, solve_joints, Joint};
+use crate::material::PhysicsMaterial;
+use crate::quad_trees::{QuadTreeDetect, Quadtree, WorldBounds};
+use crate::replay::SimMode;
+use crate::sensor::{Sensor, StepOverlaps};
+use crate::sleep::{update_sleep, wake_pushed, wake_touched, RestingContacts, SleepState};
 
 pub struct PhysicsPlugin;
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, update_physics);
+        let settings = PhysicsSettings::default();
+        app.insert_resource(Time::<Fixed>::from_seconds(settings.time_step as f64))
+            .insert_resource(settings)
+            .init_resource::<RestingContacts>()
+            .add_systems(Update, (sync_time_step, draw_segments))
+            // Outlines of the live polygons would be drawn over the replay
+            .add_systems(Update, draw_colliders.run_if(in_state(SimMode::Live)))
+            .add_systems(FixedUpdate, update_physics.run_if(in_state(SimMode::Live)))
+            .add_systems(
+                PostUpdate,
+                interpolate_transforms.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsSettings {
+    // Length of one fixed physics step, in seconds
+    pub time_step: f32,
+    // Each step is split into this many smaller ones
+    pub substeps: usize,
+    // Solver passes over the contacts in every substep
+    pub iterations: usize,
+    // Restitution of bodies without a PhysicsMaterial
+    pub restitution: f32,
+    pub collisions: bool,
+    // Sweep fast bodies so they can't tunnel through each other
+    pub continuous: bool,
+    pub gravity: bool,
+    pub boundary: Boundary,
+    pub integrator: Integrator,
+    // Let bodies at rest sleep, skipping them until something wakes them up
+    pub sleeping: bool,
+    // Bodies slower than this are considered at rest
+    pub sleep_speed: f32,
+    // Seconds a whole island has to stay at rest before it falls asleep
+    pub time_to_sleep: f32,
+    // Bodies make up a liquid instead of colliding with each other
+    pub fluid: Option<FluidSettings>,
+}
+
+// How velocities and positions are advanced from the accelerations
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Integrator {
+    // Velocity first, then position with the new velocity. One force evaluation.
+    #[default]
+    SymplecticEuler,
+    // Two force evaluations, keeps orbits stable
+    VelocityVerlet,
+    // Four force evaluations, most accurate over short runs but slowly drifts
+    Rk4,
+}
+
+// What happens at the edge of the world
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    // Solid walls, bodies bounce off them
+    #[default]
+    Walls,
+    // Bodies leaving one side come back on the opposite one, and touch across the edge
+    Wrap,
+}
+
+impl PhysicsSettings {
+    pub fn default_material(&self) -> PhysicsMaterial {
+        PhysicsMaterial {
+            restitution: self.restitution,
+            ..default()
+        }
+    }
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        Self {
+            time_step: 0.03,
+            substeps: 1,
+            iterations: 16,
+            restitution: 0.5,
+            collisions: true,
+            continuous: true,
+            gravity: true,
+            boundary: Boundary::Walls,
+            integrator: Integrator::SymplecticEuler,
+            sleeping: true,
+            sleep_speed: 2.0,
+            time_to_sleep: 0.5,
+            fluid: None,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Physics {
+    pub mass: f32,
+    pub collider_radius: f32,
+    pub velocity: Vec3,
+    pub acceleration: Vec3,
+    // Simulated position, the Transform is interpolated between the last two steps
+    pub position: Vec3,
+    pub previous_position: Vec3,
+    // Counter-clockwise turn in radians, interpolated like the position
+    pub angle: f32,
+    pub previous_angle: f32,
+    pub angular_velocity: f32,
+    // Added up by other systems, applied over the next step and then cleared
+    pub torque: f32,
+    pub sleep: SleepState,
+}
+
+impl Physics {
+    pub fn new(position: Vec3, velocity: Vec3, mass: f32, collider_radius: f32) -> Self {
+        Self {
+            mass,
+            collider_radius,
+            velocity,
+            acceleration: Vec3::ZERO,
+            position,
+            previous_position: position,
+            angle: 0.0,
+            previous_angle: 0.0,
+            angular_velocity: 0.0,
+            torque: 0.0,
+            sleep: SleepState::default(),
+        }
+    }
+
+    // Call after changing the velocity of a body from outside the solver
+    pub fn wake(&mut self) {
+        self.sleep.wake();
+    }
+}
+
+// Static bodies never move, kinematic ones follow their velocity and ignore
+// collisions and forces. Both push dynamic bodies as if their mass was infinite.
+#[derive(Component, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RigidBodyKind {
+    #[default]
+    Dynamic,
+    Static,
+    Kinematic,
+}
+
+// Static line collider, bodies bounce off both of its sides
+#[derive(Component, Deserialize, Debug, Clone, Copy)]
+pub struct Segment {
+    pub start: Vec2,
+    pub end: Vec2,
+}
+
+impl Segment {
+    pub fn wall(&self, entity: Entity, material: PhysicsMaterial) -> Wall {
+        Wall {
+            entity,
+            start: self.start.extend(0.0),
+            end: self.end.extend(0.0),
+            material,
+        }
     }
 }
-fn update_physics(
-    mut query: Query<(Entity, &mut Transform, &mut Physics), With<QuadTreeDetect>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
 
-    quadtree: Res<Quadtree>,
+// Segment along an edge of the world, spawned by the scenario when the world has walls
+#[derive(Component, Debug, Clone, Copy)]
+pub struct WorldEdge;
+
+// A segment as seen by the solver
+#[derive(Debug, Clone, Copy)]
+pub struct Wall {
+    pub entity: Entity,
+    pub start: Vec3,
+    pub end: Vec3,
+    pub material: PhysicsMaterial,
+}
+
+impl Wall {
+    fn closest_point(&self, point: Vec3) -> Vec3 {
+        let along = self.end - self.start;
+        let t = (point - self.start).dot(along) / along.length_squared().max(f32::EPSILON);
+        self.start + along * t.clamp(0.0, 1.0)
+    }
+
+    // Static body standing in for the wall at `point`, so the contact solver can be reused
+    fn body_at(&self, point: Vec3) -> Body {
+        Body {
+            entity: self.entity,
+            position: point,
+            velocity: Vec3::ZERO,
+            acceleration: Vec3::ZERO,
+            mass: f32::INFINITY,
+            radius: 0.0,
+            shape: Shape::Circle,
+            angle: 0.0,
+            angular_velocity: 0.0,
+            inertia: f32::INFINITY,
+            torque: 0.0,
+            material: self.material,
+            kind: RigidBodyKind::Static,
+            sleep: SleepState::default(),
+            sensor: false,
+        }
+    }
+
+    // Static body covering the whole wall, for colliding with polygons
+    fn segment_body(&self) -> Body {
+        let middle = (self.start + self.end) / 2.0;
+        let half = (self.end - middle).xy();
+        Body {
+            radius: half.length(),
+            shape: Shape::Polygon(Polygon::segment(half)),
+            ..self.body_at(middle)
+        }
+    }
+}
+
+// Bottom, right, top and left edges of the world. Each is its own entity, so a body
+// in a corner reports a contact with both walls.
+pub fn world_edges(bounds: &WorldBounds) -> [Segment; 4] {
+    let rect = bounds.rect();
+    let corners = [
+        rect.min,
+        Vec2::new(rect.max.x, rect.min.y),
+        rect.max,
+        Vec2::new(rect.min.x, rect.max.y),
+    ];
+    std::array::from_fn(|i| Segment {
+        start: corners[i],
+        end: corners[(i + 1) % 4],
+    })
+}
+
+// Shortest offset from one point to another, going across the edges when the world wraps
+pub fn separation(from: Vec3, to: Vec3, bounds: &WorldBounds, boundary: Boundary) -> Vec3 {
+    let mut offset = to - from;
+    if boundary == Boundary::Wrap {
+        let size = bounds.half_extents * 2.0;
+        offset.x -= size.x * (offset.x / size.x).round();
+        offset.y -= size.y * (offset.y / size.y).round();
+    }
+    offset
+}
+
+// Copy of a body the step works on, so the solver can run without the ECS
+#[derive(Debug, Clone, Copy)]
+pub struct Body {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub acceleration: Vec3,
+    pub mass: f32,
+    // Bounding radius for shapes other than circles
+    pub radius: f32,
+    pub shape: Shape,
+    pub angle: f32,
+    pub angular_velocity: f32,
+    // Moment of inertia around the body position
+    pub inertia: f32,
+    pub torque: f32,
+    pub material: PhysicsMaterial,
+    pub kind: RigidBodyKind,
+    pub sleep: SleepState,
+    // Only reports overlaps, see Sensor
+    pub sensor: bool,
+}
+
+impl Body {
+    pub fn from_physics(
+        entity: Entity,
+        physics: &Physics,
+        shape: Option<&ColliderShape>,
+        material: PhysicsMaterial,
+        kind: RigidBodyKind,
+    ) -> Self {
+        Self {
+            entity,
+            position: physics.position,
+            velocity: physics.velocity,
+            acceleration: physics.acceleration,
+            mass: physics.mass,
+            radius: shape.map_or(physics.collider_radius, ColliderShape::bounding_radius),
+            shape: shape.map_or(Shape::Circle, ColliderShape::shape),
+            angle: physics.angle,
+            angular_velocity: physics.angular_velocity,
+            inertia: shape.map_or(
+                circle_inertia(physics.mass, physics.collider_radius),
+                |shape| shape.moment_of_inertia(physics.mass),
+            ),
+            torque: physics.torque,
+            material,
+            kind,
+            sleep: physics.sleep,
+            sensor: false,
+        }
+    }
+
+    // Sleeping bodies are as immovable as static ones until they wake up
+    pub fn inverse_mass(&self) -> f32 {
+        if self.is_dynamic() {
+            1.0 / self.mass
+        } else {
+            0.0
+        }
+    }
+
+    pub fn inverse_inertia(&self) -> f32 {
+        if self.is_dynamic() && self.inertia > 0.0 {
+            1.0 / self.inertia
+        } else {
+            0.0
+        }
+    }
+
+    // Velocity of the point `arm` away from the body position, spin included
+    pub fn velocity_at(&self, arm: Vec3) -> Vec3 {
+        self.velocity + Vec3::new(-arm.y, arm.x, 0.0) * self.angular_velocity
+    }
+
+    // Shape turned by the angle of the body
+    pub fn posed_shape(&self) -> Shape {
+        self.shape.rotated(self.angle)
+    }
+
+    // Moved by the solver: dynamic and awake
+    pub fn is_dynamic(&self) -> bool {
+        self.kind == RigidBodyKind::Dynamic && !self.sleep.asleep
+    }
+}
+
+fn sync_time_step(settings: Res<PhysicsSettings>, mut time: ResMut<Time<Fixed>>) {
+    if settings.is_changed() {
+        time.set_timestep_seconds(settings.time_step as f64);
+    }
+}
+
+type BodyComponents<'a> = (
+    Entity,
+    &'a mut Physics,
+    Option<&'a PhysicsMaterial>,
+    Option<&'a RigidBodyKind>,
+    Option<&'a ColliderShape>,
+    Has<Sensor>,
+);
+
+// Everything acting on bodies besides each other
+#[derive(SystemParam)]
+pub struct Surroundings<'w, 's> {
+    segments: Query<'w, 's, (Entity, &'static Segment, Option<&'static PhysicsMaterial>)>,
+    joints: Query<'w, 's, &'static Joint>,
+    gravity: Res<'w, GravitySettings>,
+    attractors: Query<'w, 's, (Ref<'static, Attractor>, Ref<'static, Transform>)>,
+    removed_attractors: RemovedComponents<'w, 's, Attractor>,
+}
+
+// Where update_physics leaves what the step found for the systems after it
+#[derive(SystemParam)]
+pub struct StepReports<'w> {
+    contacts: ResMut<'w, StepContacts>,
+    overlaps: ResMut<'w, StepOverlaps>,
+}
+
+// What happened during a step besides the bodies moving
+#[derive(Debug, Default)]
+pub struct StepOutput {
+    pub contacts: Vec<Contact>,
+    // [sensor, entity] pairs, both ways round when two sensors overlap
+    pub overlaps: Vec<[Entity; 2]>,
+}
+
+pub fn update_physics(
+    mut query: Query<BodyComponents, With<QuadTreeDetect>>,
+    settings: Res<PhysicsSettings>,
+    bounds: Res<WorldBounds>,
+    mut quadtree: ResMut<Quadtree>,
+    mut surroundings: Surroundings,
+    mut reports: StepReports,
+    mut resting: ResMut<RestingContacts>,
 ) {
-    if !keyboard_input.pressed(KeyCode::KeyC) {
-        //look for possible collisions
-        let mut possible_collisions: HashSet<[Entity; 2]> = HashSet::new();
-        for (entity, transform_1, physics_1) in query.iter() {
-            //get possible cadidates
-            //
-            let padding = physics_1.collider_radius * 2.1;
-
-            let mut candidates: Vec<Entity> = Vec::new();
-
-            let area = Rect::new(
-                transform_1.translation.x - padding,
-                transform_1.translation.y - padding,
-                transform_1.translation.x + padding,
-                transform_1.translation.y + padding,
-            );
-            quadtree.query(area, &mut candidates);
+    // Turning gravity around, switching it on or moving an attractor can leave
+    // resting bodies hanging in the air
+    let attractors_changed = surroundings
+        .attractors
+        .iter()
+        .any(|(attractor, transform)| attractor.is_changed() || transform.is_changed())
+        || surroundings.removed_attractors.read().count() > 0;
+    let pull_changed =
+        settings.is_changed() || surroundings.gravity.is_changed() || attractors_changed;
+    let settings = *settings;
+
+    let default_material = settings.default_material();
+    let mut bodies: Vec<Body> = query
+        .iter()
+        .map(|(entity, physics, material, kind, shape, sensor)| Body {
+            sensor,
+            ..Body::from_physics(
+                entity,
+                physics,
+                shape,
+                *material.unwrap_or(&default_material),
+                kind.copied().unwrap_or_default(),
+            )
+        })
+        .collect();
+    if pull_changed {
+        for body in bodies.iter_mut() {
+            body.sleep.wake();
+        }
+    }
+    let walls: Vec<Wall> = surroundings
+        .segments
+        .iter()
+        .map(|(entity, segment, material)| {
+            segment.wall(entity, *material.unwrap_or(&default_material))
+        })
+        .collect();
+
+    let joints: Vec<Joint> = surroundings.joints.iter().copied().collect();
+    let attractors: Vec<PointAttractor> = surroundings
+        .attractors
+        .iter()
+        .map(|(attractor, transform)| attractor.at(transform.translation))
+        .collect();
+    let gravity = GravityField {
+        settings: *surroundings.gravity,
+        attractors: &attractors,
+    };
+
+    let output = step(
+        &mut bodies,
+        &walls,
+        &joints,
+        &gravity,
+        &mut quadtree,
+        &bounds,
+        &settings,
+    );
+    let mut contacts = output.contacts;
+    resting.update(&bodies, &walls, &mut contacts);
+    reports.contacts.0 = contacts;
+    reports.overlaps.0 = output.overlaps;
 
-            if candidates.len() > 1 {
-                if let Some(pos) = candidates.iter().position(|x| *x == entity) {
-                    candidates.remove(pos);
+    for ((_, mut physics, ..), body) in query.iter_mut().zip(bodies.iter()) {
+        physics.previous_position = physics.position;
+        physics.position = body.position;
+        physics.velocity = body.velocity;
+        physics.acceleration = body.acceleration;
+        physics.previous_angle = physics.angle;
+        physics.angle = body.angle;
+        physics.angular_velocity = body.angular_velocity;
+        physics.torque = 0.0;
+        physics.sleep = body.sleep;
+    }
+}
+
+// Advances the bodies by one fixed step, returning every contact and sensor overlap during it
+pub fn step(
+    bodies: &mut [Body],
+    walls: &[Wall],
+    joints: &[Joint],
+    gravity: &GravityField,
+    quadtree: &mut Quadtree,
+    bounds: &WorldBounds,
+    settings: &PhysicsSettings,
+) -> StepOutput {
+    let substeps = settings.substeps.max(1);
+    let dt = settings.time_step / substeps as f32;
+    let index: HashMap<Entity, usize> = bodies
+        .iter()
+        .enumerate()
+        .map(|(i, body)| (body.entity, i))
+        .collect();
+    let links = resolve_joints(joints, &index);
+    let joined: Vec<[usize; 2]> = links.iter().filter_map(|link| link.pair()).collect();
+
+    let gravity = if settings.gravity {
+        *gravity
+    } else {
+        GravityField::NONE
+    };
+    // Everything pushing or pulling bodies besides contacts and joints
+    let accelerate = |bodies: &mut [Body], quadtree: &Quadtree| {
+        apply_gravity(bodies, quadtree, &gravity);
+        if let Some(fluid) = &settings.fluid {
+            apply_fluid(bodies, quadtree, &index, fluid, bounds, settings.boundary);
+        }
+    };
+    // A liquid at rest still needs its pressure to hold itself up
+    let sleeping = settings.sleeping && settings.fluid.is_none();
+
+    // A pair touching in several substeps or iterations is reported once
+    let mut contacts: HashMap<[Entity; 2], Contact> = HashMap::new();
+    let mut record = |contact: Option<Contact>| {
+        if let Some(contact) = contact {
+            contacts
+                .entry(contact.entities)
+                .and_modify(|existing| {
+                    existing.impulse += contact.impulse;
+                    existing.point = contact.point;
+                    existing.normal = contact.normal;
+                    existing.depth = contact.depth;
+                })
+                .or_insert(contact);
+        }
+    };
+    let mut overlaps: HashSet<[Entity; 2]> = HashSet::new();
+    let start: Vec<(Vec3, f32)> = bodies
+        .iter()
+        .map(|body| (body.position, body.angle))
+        .collect();
+
+    for _ in 0..substeps {
+        quadtree.rebuild(
+            bounds.rect(),
+            bodies.iter().map(|body| (body.entity, body.position)),
+        );
+
+        // Waking spreads along joints like it does through contacts
+        wake_touched(bodies, &joined);
+
+        let mut possible_collisions = Vec::new();
+        // Sensors keep reporting overlaps with collisions switched off
+        if settings.collisions || bodies.iter().any(|body| body.sensor) {
+            let sweep = if settings.continuous { dt } else { 0.0 };
+            possible_collisions =
+                find_pairs(bodies, quadtree, &index, sweep, bounds, settings.boundary);
+
+            // Sensors only report overlaps, they never push or wake anything
+            let sensed: Vec<[usize; 2]>;
+            (sensed, possible_collisions) = possible_collisions
+                .into_iter()
+                .partition(|&[i, j]| bodies[i].sensor || bodies[j].sensor);
+            for [i, j] in sensed {
+                if overlapping(bodies, [i, j], bounds, settings.boundary) {
+                    for (sensor, other) in [(i, j), (j, i)] {
+                        if bodies[sensor].sensor {
+                            overlaps.insert([bodies[sensor].entity, bodies[other].entity]);
+                        }
+                    }
                 }
-                for candidate in candidates.iter() {
-                    if candidate.index() < entity.index() {
-                        possible_collisions.insert([*candidate, entity]);
-                    } else {
-                        possible_collisions.insert([entity, *candidate]);
+            }
+            if !settings.collisions || settings.fluid.is_some() {
+                // Bodies pass through each other, or pressure keeps them apart instead
+                possible_collisions.clear();
+            }
+        }
+        if settings.collisions {
+            wake_touched(bodies, &possible_collisions);
+            // Left with pairs where at least one body can move
+            possible_collisions.retain(|&[i, j]| bodies[i].is_dynamic() || bodies[j].is_dynamic());
+            if settings.continuous {
+                for pair in possible_collisions.iter() {
+                    record(sweep_contact(bodies, *pair, dt, bounds, settings.boundary));
+                }
+                for body in bodies
+                    .iter_mut()
+                    .filter(|body| body.is_dynamic() && !body.sensor)
+                {
+                    for wall in walls.iter() {
+                        record(sweep_wall(body, wall, dt));
                     }
                 }
             }
         }
 
-        for _ in 0..16 {
-            //iter possible_collisions
-            for possible_collison in possible_collisions.iter() {
-                let [(_, mut transform1, mut physics1), (_, mut transform2, mut physics2)] =
-                    query.many_mut(*possible_collison);
+        apply_springs(bodies, &links, dt, bounds, settings.boundary);
+        for _ in 0..settings.iterations {
+            for pair in possible_collisions.iter() {
+                record(solve_contact(bodies, *pair, bounds, settings.boundary));
+            }
+            if settings.collisions {
+                for body in bodies
+                    .iter_mut()
+                    .filter(|body| body.is_dynamic() && !body.sensor)
+                {
+                    for wall in walls.iter() {
+                        record(solve_wall(body, wall));
+                    }
+                }
+            }
+            solve_joints(bodies, &links, bounds, settings.boundary);
+        }
 
-                let distance = transform1.translation.distance(transform2.translation);
-                let depth = (physics1.collider_radius + physics2.collider_radius) - distance;
+        accelerate(bodies, quadtree);
+        if sleeping {
+            wake_pushed(bodies, settings);
+        }
+        integrate(
+            bodies,
+            settings.integrator,
+            dt,
+            &mut |bodies: &mut [Body]| {
+                // Later evaluations need the tree to follow the bodies
+                quadtree.rebuild(
+                    bounds.rect(),
+                    bodies.iter().map(|body| (body.entity, body.position)),
+                );
+                accelerate(bodies, quadtree);
+            },
+        );
+        apply_boundary(bodies, bounds, settings.boundary);
+    }
 
-                if depth >= 0.0 {
-                    // Calculate average restitution
-                    let restitution = 0.5;
+    if sleeping {
+        update_sleep(bodies, &start, &contacts, &joined, &index, settings);
+    }
 
-                    // Calculate relative velocity
-                    let relative_velocity = physics2.velocity - physics1.velocity;
+    StepOutput {
+        contacts: contacts.into_values().collect(),
+        overlaps: overlaps.into_iter().collect(),
+    }
+}
 
-                    // Calculate velocity component along the normal direction
-                    let collision_normal =
-                        (transform2.translation - transform1.translation).normalize();
-                    let velocity_along_normal = relative_velocity.dot(collision_normal);
+// When the world wraps, bodies near an edge also look for neighbours on the other side
+pub fn wrap_shifts(bounds: &WorldBounds, boundary: Boundary) -> Vec<Vec2> {
+    match boundary {
+        Boundary::Walls => vec![Vec2::ZERO],
+        Boundary::Wrap => (-1..=1)
+            .flat_map(|x| (-1..=1).map(move |y| Vec2::new(x as f32, y as f32)))
+            .map(|shift| shift * bounds.rect().size())
+            .collect(),
+    }
+}
 
-                    // Skip if velocities are separating
-                    if velocity_along_normal > 0.0 {
-                        continue;
-                    }
+// Adds the entities within `padding` of `position` on both axes to `candidates`
+pub fn query_around(
+    quadtree: &Quadtree,
+    position: Vec3,
+    padding: f32,
+    shifts: &[Vec2],
+    bounds: &WorldBounds,
+    candidates: &mut Vec<Entity>,
+) {
+    let world = bounds.rect();
+    for shift in shifts.iter() {
+        let area = Rect::new(
+            position.x - padding,
+            position.y - padding,
+            position.x + padding,
+            position.y + padding,
+        );
+        let area = Rect::from_corners(area.min + *shift, area.max + *shift);
+        if !area.intersect(world).is_empty() {
+            quadtree.query(area, candidates);
+        }
+    }
+}
 
-                    // Calculate impulse scalar
-                    let impulse_scalar = -(1.0 + restitution) * velocity_along_normal
-                        / (1.0 / physics1.mass + 1.0 / physics2.mass);
+// Pairs of bodies that can touch within `sweep` seconds, lowest index first
+fn find_pairs(
+    bodies: &[Body],
+    quadtree: &Quadtree,
+    index: &HashMap<Entity, usize>,
+    sweep: f32,
+    bounds: &WorldBounds,
+    boundary: Boundary,
+) -> Vec<[usize; 2]> {
+    // Half size of the square each body can reach during the sweep
+    let extents: Vec<f32> = bodies
+        .iter()
+        .map(|body| body.radius * 1.05 + body.velocity.length() * sweep)
+        .collect();
 
-                    // Apply impulse to the entities' velocities
-                    let impulse = collision_normal * impulse_scalar;
+    // Sleeping and static bodies get found by the ones moving around them, sensors
+    // look for themselves so they also see the resting ones
+    let resting =
+        |body: &Body| (body.sleep.asleep || body.kind == RigidBodyKind::Static) && !body.sensor;
+    // Only resting bodies reaching further than some searching body have to search too
+    let shortest_search = bodies
+        .iter()
+        .zip(extents.iter())
+        .filter(|(body, _)| !resting(body))
+        .map(|(_, extent)| *extent)
+        .fold(f32::INFINITY, f32::min);
 
-                    let v1 = physics1.velocity - impulse / physics1.mass;
-                    let v2 = physics2.velocity + impulse / physics2.mass;
+    let shifts = wrap_shifts(bounds, boundary);
 
-                    physics1.velocity = v1;
-                    physics2.velocity = v2;
-                    //fix their positions
-                    let total_inverse_mass = 1.0 / physics1.mass + 1.0 / physics2.mass;
+    let mut possible_collisions: HashSet<[usize; 2]> = HashSet::new();
+    let mut candidates: Vec<Entity> = Vec::new();
+    for (i, body) in bodies.iter().enumerate() {
+        if resting(body) && extents[i] <= shortest_search {
+            continue;
+        }
 
-                    let n_pos1 =
-                        collision_normal * (depth * (1.0 / physics1.mass) / total_inverse_mass);
-                    let n_pos2 =
-                        collision_normal * (depth * (1.0 / physics2.mass) / total_inverse_mass);
+        // The quadtree only holds centres. Twice our own reach finds every body reaching
+        // less far than us, the ones reaching further find us themselves.
+        candidates.clear();
+        query_around(
+            quadtree,
+            body.position,
+            extents[i] * 2.0,
+            &shifts,
+            bounds,
+            &mut candidates,
+        );
 
-                    // Correction to push them apart
-                    transform1.translation -= n_pos1;
-                    transform2.translation += n_pos2;
-                    // Apply impulse
-                }
+        for candidate in candidates.iter() {
+            let Some(&j) = index.get(candidate) else {
+                continue;
+            };
+            // Immovable bodies can't push each other
+            let other = &bodies[j];
+            if i == j
+                || (resting(body) && resting(other))
+                || !(body.kind == RigidBodyKind::Dynamic
+                    || other.kind == RigidBodyKind::Dynamic
+                    || body.sensor
+                    || other.sensor)
+            {
+                continue;
+            }
+            if can_touch(body, other, sweep, bounds, boundary) {
+                possible_collisions.insert([i.min(j), i.max(j)]);
             }
         }
     }
 
-    if !keyboard_input.pressed(KeyCode::KeyG) {
-        let mut huntsman: Vec<(f32, Vec3)> = Vec::new();
-        quadtree.huntsman(&mut huntsman);
+    let mut pairs: Vec<[usize; 2]> = possible_collisions.into_iter().collect();
+    pairs.sort_unstable();
+    pairs
+}
 
-        for (_, transform, mut physics) in query.iter_mut() {
-            let mut n_acc = Vec3::ZERO;
-            for (n, pos) in huntsman.iter() {
-                let distance = transform.translation.distance_squared(*pos).min(1.0);
-                let normal = (transform.translation - *pos).normalize();
+// Whether the box around the second body, swept by its velocity relative to the first,
+// comes within reach of the first during `sweep` seconds
+fn can_touch(
+    body1: &Body,
+    body2: &Body,
+    sweep: f32,
+    bounds: &WorldBounds,
+    boundary: Boundary,
+) -> bool {
+    let offset = separation(body1.position, body2.position, bounds, boundary).xy();
+    let moved = offset + (body2.velocity - body1.velocity).xy() * sweep;
+    let reach = Vec2::splat((body1.radius + body2.radius) * 1.05);
+    let swept = Rect::from_corners(offset.min(moved) - reach, offset.max(moved) + reach);
+    swept.contains(Vec2::ZERO)
+}
 
-                n_acc += normal * (1.0 / distance) * *n;
-            }
-            physics.acceleration = n_acc * -0.0002;
+// Whether two bodies overlap, without doing anything about it
+fn overlapping(
+    bodies: &[Body],
+    [i, j]: [usize; 2],
+    bounds: &WorldBounds,
+    boundary: Boundary,
+) -> bool {
+    let (body1, body2) = (&bodies[i], &bodies[j]);
+    let offset = separation(body1.position, body2.position, bounds, boundary);
+    if body1.shape == Shape::Circle && body2.shape == Shape::Circle {
+        let radius = body1.radius + body2.radius;
+        return offset.length_squared() < radius * radius;
+    }
+    collide(
+        &body1.posed_shape(),
+        body1.radius,
+        &body2.posed_shape(),
+        body2.radius,
+        offset.xy(),
+    )
+    .is_some()
+}
+
+// Bounce and friction impulses between two touching bodies, `collision_normal` points
+// from the first to the second and the arms from each body position to the contact.
+// Returns the normal impulse, or None without doing anything if they are separating.
+fn apply_contact_impulse(
+    body1: &mut Body,
+    body2: &mut Body,
+    collision_normal: Vec3,
+    [arm1, arm2]: [Vec3; 2],
+) -> Option<f32> {
+    // Calculate relative velocity of the touching points
+    let relative_velocity = body2.velocity_at(arm2) - body1.velocity_at(arm1);
+
+    // Calculate velocity component along the normal direction
+    let velocity_along_normal = relative_velocity.dot(collision_normal);
+
+    // Skip if velocities are separating
+    if velocity_along_normal > 0.0 {
+        return None;
+    }
+
+    let total_inverse_mass = effective_inverse_mass(body1, body2, collision_normal, [arm1, arm2]);
+    if total_inverse_mass == 0.0 {
+        return None;
+    }
+
+    let (restitution, static_friction, dynamic_friction) = body1.material.mix(&body2.material);
+
+    // Calculate impulse scalar
+    let impulse_scalar = -(1.0 + restitution) * velocity_along_normal / total_inverse_mass;
+
+    // Apply impulse to the entities' velocities
+    apply_impulse(
+        body1,
+        body2,
+        collision_normal * impulse_scalar,
+        [arm1, arm2],
+    );
+
+    // Friction works against the sliding left after the bounce
+    let relative_velocity = body2.velocity_at(arm2) - body1.velocity_at(arm1);
+    let sliding = relative_velocity - collision_normal * relative_velocity.dot(collision_normal);
+    let tangent = sliding.normalize_or_zero();
+    if tangent == Vec3::ZERO {
+        return Some(impulse_scalar);
+    }
+
+    // Impulse that would stop the sliding completely
+    let tangent_scalar = -relative_velocity.dot(tangent)
+        / effective_inverse_mass(body1, body2, tangent, [arm1, arm2]);
+
+    // Coulomb friction: stick while the needed impulse is small enough, slide otherwise
+    let friction = if tangent_scalar.abs() <= impulse_scalar * static_friction {
+        tangent * tangent_scalar
+    } else {
+        tangent * -impulse_scalar * dynamic_friction
+    };
+    apply_impulse(body1, body2, friction, [arm1, arm2]);
+
+    Some(impulse_scalar)
+}
+
+// How easily the touching points get moved along `direction`, off centre hits also spin the bodies
+fn effective_inverse_mass(
+    body1: &Body,
+    body2: &Body,
+    direction: Vec3,
+    [arm1, arm2]: [Vec3; 2],
+) -> f32 {
+    body1.inverse_mass()
+        + body2.inverse_mass()
+        + arm1.xy().perp_dot(direction.xy()).powi(2) * body1.inverse_inertia()
+        + arm2.xy().perp_dot(direction.xy()).powi(2) * body2.inverse_inertia()
+}
+
+// `impulse` goes to the second body and its opposite to the first
+fn apply_impulse(body1: &mut Body, body2: &mut Body, impulse: Vec3, [arm1, arm2]: [Vec3; 2]) {
+    body1.velocity -= impulse * body1.inverse_mass();
+    body1.angular_velocity -= arm1.xy().perp_dot(impulse.xy()) * body1.inverse_inertia();
+    body2.velocity += impulse * body2.inverse_mass();
+    body2.angular_velocity += arm2.xy().perp_dot(impulse.xy()) * body2.inverse_inertia();
+}
+
+fn solve_contact(
+    bodies: &mut [Body],
+    [i, j]: [usize; 2],
+    bounds: &WorldBounds,
+    boundary: Boundary,
+) -> Option<Contact> {
+    let (head, tail) = bodies.split_at_mut(j);
+    let (body1, body2) = (&mut head[i], &mut tail[0]);
+    let offset = separation(body1.position, body2.position, bounds, boundary);
+    if body1.shape == Shape::Circle && body2.shape == Shape::Circle {
+        return solve_pair(body1, body2, offset);
+    }
+
+    let manifold = collide(
+        &body1.posed_shape(),
+        body1.radius,
+        &body2.posed_shape(),
+        body2.radius,
+        offset.xy(),
+    )?;
+    resolve(
+        body1,
+        body2,
+        manifold.normal.extend(0.0),
+        manifold.centre().extend(0.0),
+        offset,
+        manifold.depth(),
+    )
+}
+
+// Pushes two overlapping bodies apart, `offset` goes from the first to the second.
+// Returns the contact if they touch, even when already separating.
+fn solve_pair(body1: &mut Body, body2: &mut Body, offset: Vec3) -> Option<Contact> {
+    let distance = offset.length();
+    let depth = (body1.radius + body2.radius) - distance;
+    if depth < 0.0 || distance <= f32::EPSILON {
+        return None;
+    }
+
+    let collision_normal = offset / distance;
+    let arm = collision_normal * body1.radius;
+    resolve(body1, body2, collision_normal, arm, offset, depth)
+}
+
+// Bounces and separates two bodies touching `arm` away from the first one
+fn resolve(
+    body1: &mut Body,
+    body2: &mut Body,
+    collision_normal: Vec3,
+    arm: Vec3,
+    offset: Vec3,
+    depth: f32,
+) -> Option<Contact> {
+    let mut contact = Contact {
+        entities: [body1.entity, body2.entity],
+        point: body1.position + arm,
+        normal: collision_normal,
+        impulse: 0.0,
+        depth,
+    };
+    let arms = [arm, arm - offset];
+    let Some(impulse) = apply_contact_impulse(body1, body2, collision_normal, arms) else {
+        return Some(contact);
+    };
+    contact.impulse = impulse;
+
+    let total_inverse_mass = body1.inverse_mass() + body2.inverse_mass();
+
+    //fix their positions
+    let n_pos1 = collision_normal * (depth * body1.inverse_mass() / total_inverse_mass);
+    let n_pos2 = collision_normal * (depth * body2.inverse_mass() / total_inverse_mass);
+
+    // Correction to push them apart
+    body1.position -= n_pos1;
+    body2.position += n_pos2;
+
+    Some(contact)
+}
+
+fn solve_wall(body: &mut Body, wall: &Wall) -> Option<Contact> {
+    if body.shape != Shape::Circle {
+        let mut wall_body = wall.segment_body();
+        let offset = body.position - wall_body.position;
+        let manifold = collide(
+            &wall_body.shape,
+            0.0,
+            &body.posed_shape(),
+            body.radius,
+            offset.xy(),
+        )?;
+        return resolve(
+            &mut wall_body,
+            body,
+            manifold.normal.extend(0.0),
+            manifold.centre().extend(0.0),
+            offset,
+            manifold.depth(),
+        );
+    }
+
+    let point = wall.closest_point(body.position);
+    let mut wall_body = wall.body_at(point);
+    let offset = body.position - point;
+    solve_pair(&mut wall_body, body, offset)
+}
+
+// Earliest time in 0..=dt at which two circles moving at constant velocity touch.
+// `offset` and `velocity` are those of the second circle relative to the first.
+pub fn time_of_impact(offset: Vec3, velocity: Vec3, radius: f32, dt: f32) -> Option<f32> {
+    let c = offset.length_squared() - radius * radius;
+    if c <= 0.0 {
+        // Already touching
+        return Some(0.0);
+    }
+
+    let b = offset.dot(velocity);
+    if b >= 0.0 {
+        // Moving apart
+        return None;
+    }
+
+    let a = velocity.length_squared();
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        // Missing each other
+        return None;
+    }
+
+    let t = (-b - discriminant.sqrt()) / a;
+    (t <= dt).then_some(t)
+}
+
+// Bounces two bodies that are apart now but would cross each other during this substep
+fn sweep_contact(
+    bodies: &mut [Body],
+    [i, j]: [usize; 2],
+    dt: f32,
+    bounds: &WorldBounds,
+    boundary: Boundary,
+) -> Option<Contact> {
+    let (head, tail) = bodies.split_at_mut(j);
+    let (body1, body2) = (&mut head[i], &mut tail[0]);
+    if body1.shape != Shape::Circle || body2.shape != Shape::Circle {
+        // Only circles are swept, other shapes rely on substeps
+        return None;
+    }
+
+    let offset = separation(body1.position, body2.position, bounds, boundary);
+    let radius = body1.radius + body2.radius;
+    if offset.length_squared() <= radius * radius {
+        // Overlapping ones are handled by solve_contact
+        return None;
+    }
+    let (velocity1, velocity2) = (body1.velocity, body2.velocity);
+    let toi = time_of_impact(offset, velocity2 - velocity1, radius, dt)?;
+
+    // Offset between them when they touch
+    let collision_normal = (offset + (velocity2 - velocity1) * toi).normalize_or_zero();
+    let arms = [
+        collision_normal * body1.radius,
+        -collision_normal * body2.radius,
+    ];
+    let impulse = apply_contact_impulse(body1, body2, collision_normal, arms)?;
+    let contact = Contact {
+        entities: [body1.entity, body2.entity],
+        point: body1.position + velocity1 * toi + collision_normal * body1.radius,
+        normal: collision_normal,
+        impulse,
+        depth: 0.0,
+    };
+
+    // Rewind along the new velocity, so integrating the whole substep leaves
+    // them where they would be after bouncing at the time of impact
+    body1.position += (velocity1 - body1.velocity) * toi;
+    body2.position += (velocity2 - body2.velocity) * toi;
+
+    Some(contact)
+}
+
+// Bounces a body that would cross a wall during this substep
+fn sweep_wall(body: &mut Body, wall: &Wall, dt: f32) -> Option<Contact> {
+    if body.shape != Shape::Circle {
+        return None;
+    }
+
+    let along = (wall.end - wall.start).normalize_or_zero();
+    if along == Vec3::ZERO {
+        return None;
+    }
+
+    // Normal of the side the body is on
+    let mut normal = Vec3::new(-along.y, along.x, 0.0);
+    let mut distance = (body.position - wall.start).dot(normal);
+    if distance < 0.0 {
+        normal = -normal;
+        distance = -distance;
+    }
+    if distance <= body.radius {
+        // Overlapping ones are handled by solve_wall
+        return None;
+    }
+
+    let closing_speed = -body.velocity.dot(normal);
+    if closing_speed <= 0.0 {
+        return None;
+    }
+    let toi = (distance - body.radius) / closing_speed;
+    if toi > dt {
+        return None;
+    }
+
+    // Passing beside the ends of the wall
+    let contact = body.position + body.velocity * toi;
+    let point = wall.closest_point(contact);
+    if contact.distance(point) > body.radius * 1.001 {
+        return None;
+    }
+
+    let velocity = body.velocity;
+    let mut wall_body = wall.body_at(point);
+    let arms = [Vec3::ZERO, -normal * body.radius];
+    let impulse = apply_contact_impulse(&mut wall_body, body, normal, arms)?;
+    body.position += (velocity - body.velocity) * toi;
+
+    Some(Contact {
+        entities: [wall.entity, body.entity],
+        point,
+        normal,
+        impulse,
+        depth: 0.0,
+    })
+}
+
+// Advances the bodies by `dt`. Their acceleration has to be up to date, `accelerate`
+// recomputes it after the positions moved, for integrators needing more evaluations.
+pub fn integrate(
+    bodies: &mut [Body],
+    integrator: Integrator,
+    dt: f32,
+    accelerate: &mut impl FnMut(&mut [Body]),
+) {
+    for body in bodies.iter_mut() {
+        if body.kind == RigidBodyKind::Kinematic {
+            body.position += body.velocity * dt;
+            body.angle += body.angular_velocity * dt;
         }
     }
 
-    //step dy
-    for (_, mut transform, mut physics) in query.iter_mut() {
-        let velocity = physics.velocity + physics.acceleration * 0.03;
-        physics.velocity = velocity;
-        transform.translation += velocity * 0.03;
+    // Torque is held over the substep, so every integrator turns bodies the same way
+    for body in bodies.iter_mut().filter(|body| body.is_dynamic()) {
+        body.angular_velocity += body.torque * body.inverse_inertia() * dt;
+        body.angle += body.angular_velocity * dt;
+    }
+
+    match integrator {
+        Integrator::SymplecticEuler => {
+            for body in bodies.iter_mut().filter(|body| body.is_dynamic()) {
+                body.velocity += body.acceleration * dt;
+                body.position += body.velocity * dt;
+            }
+        }
+        Integrator::VelocityVerlet => {
+            let start: Vec<Vec3> = bodies.iter().map(|body| body.acceleration).collect();
+            for body in bodies.iter_mut().filter(|body| body.is_dynamic()) {
+                body.position += body.velocity * dt + body.acceleration * (0.5 * dt * dt);
+            }
+            accelerate(bodies);
+            for (body, acceleration) in bodies.iter_mut().zip(start) {
+                if body.is_dynamic() {
+                    body.velocity += (acceleration + body.acceleration) * (0.5 * dt);
+                }
+            }
+        }
+        Integrator::Rk4 => {
+            let start: Vec<(Vec3, Vec3)> = bodies
+                .iter()
+                .map(|body| (body.position, body.velocity))
+                .collect();
+            // Weighted sum of the position and velocity slopes
+            let mut slopes = vec![(Vec3::ZERO, Vec3::ZERO); bodies.len()];
+
+            for (stage, (weight, offset)) in [(1.0, 0.5), (2.0, 0.5), (2.0, 1.0), (1.0, 0.0)]
+                .into_iter()
+                .enumerate()
+            {
+                if stage > 0 {
+                    accelerate(bodies);
+                }
+                for ((body, slope), (position, velocity)) in
+                    bodies.iter_mut().zip(slopes.iter_mut()).zip(start.iter())
+                {
+                    if !body.is_dynamic() {
+                        continue;
+                    }
+                    slope.0 += body.velocity * weight;
+                    slope.1 += body.acceleration * weight;
+                    // State the next stage is evaluated at
+                    body.position = *position + body.velocity * (dt * offset);
+                    body.velocity = *velocity + body.acceleration * (dt * offset);
+                }
+            }
 
-        if transform.translation.x.abs() > X_EXTENT {
-            physics.velocity.x *= 0.7;
-            transform.translation.x *= -0.999;
+            for ((body, slope), (position, velocity)) in bodies.iter_mut().zip(slopes).zip(start) {
+                if body.is_dynamic() {
+                    body.position = position + slope.0 * (dt / 6.0);
+                    body.velocity = velocity + slope.1 * (dt / 6.0);
+                }
+            }
         }
-        if transform.translation.y.abs() > Y_EXTENT {
-            physics.velocity.y *= 0.7;
-            transform.translation.y *= -0.999;
+    }
+}
+
+fn apply_boundary(bodies: &mut [Body], bounds: &WorldBounds, boundary: Boundary) {
+    for body in bodies.iter_mut().filter(|body| body.is_dynamic()) {
+        match boundary {
+            Boundary::Wrap => {
+                let half = bounds.half_extents;
+                body.position.x = (body.position.x + half.x).rem_euclid(half.x * 2.0) - half.x;
+                body.position.y = (body.position.y + half.y).rem_euclid(half.y * 2.0) - half.y;
+            }
+            Boundary::Walls => {
+                // Last resort for bodies the position correction pushed through a wall
+                let limit = (bounds.half_extents - Vec2::splat(body.radius)).max(Vec2::ZERO);
+                let inside = body.position.xy().clamp(-limit, limit);
+                for axis in 0..2 {
+                    if inside[axis] != body.position[axis] {
+                        if body.velocity[axis] * body.position[axis] > 0.0 {
+                            body.velocity[axis] *= -body.material.restitution;
+                        }
+                        body.position[axis] = inside[axis];
+                    }
+                }
+            }
         }
     }
 }
 
-#[derive(Component, Debug)]
-pub struct Physics {
-    pub mass: f32,
-    pub collider_radius: f32,
-    pub velocity: Vec3,
-    pub acceleration: Vec3,
+// The edges of the world are already outlined by the quadtree
+fn draw_segments(mut gizmos: Gizmos, segments: Query<&Segment, Without<WorldEdge>>) {
+    for segment in segments.iter() {
+        gizmos.line_2d(segment.start, segment.end, Color::WHITE);
+    }
+}
+
+// Draws the bodies between their last two simulated positions
+fn interpolate_transforms(
+    mut query: Query<(&Physics, Option<&ColliderShape>, &mut Transform)>,
+    time: Res<Time<Fixed>>,
+    bounds: Res<WorldBounds>,
+) {
+    let alpha = time.overstep_fraction();
+    for (physics, shape, mut transform) in query.iter_mut() {
+        // Don't slide across the screen when a body wraps around the world edge
+        let jump = physics.position.distance(physics.previous_position);
+        transform.translation = if jump > bounds.half_extents.min_element() {
+            physics.position
+        } else {
+            physics.previous_position.lerp(physics.position, alpha)
+        };
+
+        // Sprites of turned boxes start out turned
+        let angle = physics.previous_angle + (physics.angle - physics.previous_angle) * alpha;
+        transform.rotation = Quat::from_rotation_z(angle + shape.map_or(0.0, ColliderShape::angle));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::collider::ColliderShape;
+
+    // Dynamic circle with the mass of its area
+    pub fn body(index: u32, position: Vec2, velocity: Vec2, radius: f32) -> Body {
+        let mass = radius * radius * std::f32::consts::PI;
+        Body {
+            entity: Entity::from_raw(index),
+            position: position.extend(0.0),
+            velocity: velocity.extend(0.0),
+            acceleration: Vec3::ZERO,
+            mass,
+            radius,
+            shape: Shape::Circle,
+            angle: 0.0,
+            angular_velocity: 0.0,
+            inertia: circle_inertia(mass, radius),
+            torque: 0.0,
+            material: PhysicsMaterial::default(),
+            kind: RigidBodyKind::Dynamic,
+            sleep: default(),
+            sensor: false,
+        }
+    }
+
+    // Body with the collider of `shape` and the mass of its area
+    pub fn shaped(index: u32, position: Vec2, shape: &ColliderShape) -> Body {
+        Body {
+            shape: shape.shape(),
+            mass: shape.area(),
+            inertia: shape.moment_of_inertia(shape.area()),
+            ..body(index, position, Vec2::ZERO, shape.bounding_radius())
+        }
+    }
+
+    pub fn bounds(half_extent: f32) -> WorldBounds {
+        WorldBounds {
+            half_extents: Vec2::splat(half_extent),
+        }
+    }
+
+    // Walls along the edges of `bounds`, like the ones scenarios spawn
+    pub fn edge_walls(bounds: &WorldBounds) -> Vec<Wall> {
+        world_edges(bounds)
+            .iter()
+            .enumerate()
+            .map(|(i, edge)| edge.wall(Entity::from_raw(u32::MAX - 1 - i as u32), default()))
+            .collect()
+    }
+
+    // Runs `steps` steps in a big empty world, returning what the last one found
+    pub fn run(bodies: &mut [Body], settings: &PhysicsSettings, steps: usize) -> StepOutput {
+        let mut quadtree = Quadtree::default();
+        let mut output = StepOutput::default();
+        for _ in 0..steps {
+            output = step(
+                bodies,
+                &[],
+                &[],
+                &GravityField::NONE,
+                &mut quadtree,
+                &bounds(1000.0),
+                settings,
+            );
+        }
+        output
+    }
+
+    // Two radius 1 particles at the maximum spawn speed close a 30 unit gap every step,
+    // far more than their size, so without sweeping they jump over each other
+    #[test]
+    fn head_on_particles_do_not_tunnel() {
+        let settings = PhysicsSettings {
+            gravity: false,
+            ..default()
+        };
+        let start = || {
+            [
+                body(0, Vec2::new(-10.0, 0.0), Vec2::new(500.0, 0.0), 1.0),
+                body(1, Vec2::new(10.0, 0.0), Vec2::new(-500.0, 0.0), 1.0),
+            ]
+        };
+
+        let mut discrete = start();
+        let discrete_settings = PhysicsSettings {
+            continuous: false,
+            ..settings
+        };
+        run(&mut discrete, &discrete_settings, 4);
+        assert!(
+            discrete[0].position.x > discrete[1].position.x,
+            "expected the discrete solver to tunnel"
+        );
+
+        let mut swept = start();
+        run(&mut swept, &settings, 4);
+        assert!(
+            swept[0].position.x < swept[1].position.x,
+            "particles passed through each other: {:?}",
+            swept
+        );
+        assert!(swept[0].velocity.x < 0.0 && swept[1].velocity.x > 0.0);
+    }
+
+    // One fast particle in a grid of slow ones only pairs up with the ones along its way
+    #[test]
+    fn fast_bodies_do_not_widen_every_query() {
+        let mut bodies: Vec<Body> = (0..400)
+            .map(|i| {
+                let position = Vec2::new((i % 20) as f32, (i / 20) as f32) * 10.0 - 95.0;
+                body(i, position, Vec2::ZERO, 1.0)
+            })
+            .collect();
+        bodies[210].velocity = Vec3::new(1000.0, 0.0, 0.0);
+
+        let bounds = bounds(100.0);
+        let mut quadtree = Quadtree::default();
+        quadtree.rebuild(
+            bounds.rect(),
+            bodies.iter().map(|body| (body.entity, body.position)),
+        );
+        let index: HashMap<Entity, usize> = bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| (body.entity, i))
+            .collect();
+
+        let pairs = find_pairs(&bodies, &quadtree, &index, 0.03, &bounds, Boundary::Walls);
+        // 30 units to the right covers the next three particles in its row
+        let expected: Vec<[usize; 2]> = (211..214).map(|j| [210, j]).collect();
+        assert_eq!(pairs, expected);
+    }
+
+    // Small particles scattered over a much bigger static one all have to be reported
+    // touching it. They reach less far than the big one, so it has to find them itself.
+    #[test]
+    fn small_bodies_find_large_ones() {
+        let settings = PhysicsSettings {
+            gravity: false,
+            continuous: false,
+            sleeping: false,
+            iterations: 1,
+            ..default()
+        };
+        let mut rgen = StdRng::seed_from_u64(3);
+        let big_radius = 20.0;
+        let mut bodies = vec![Body {
+            kind: RigidBodyKind::Static,
+            ..body(0, Vec2::ZERO, Vec2::ZERO, big_radius)
+        }];
+        for i in 1..200 {
+            // Anywhere from deep inside to just past the edge
+            let distance = rgen.gen_range(1.0..big_radius + 2.0);
+            let position = Vec2::from_angle(rgen.gen_range(0.0..std::f32::consts::TAU)) * distance;
+            bodies.push(body(i, position, Vec2::ZERO, 0.5));
+        }
+        let overlapping: Vec<Entity> = bodies[1..]
+            .iter()
+            .filter(|body| body.position.length() < big_radius + body.radius)
+            .map(|body| body.entity)
+            .collect();
+
+        let contacts = run(&mut bodies, &settings, 1).contacts;
+        let big = bodies[0].entity;
+        for entity in overlapping.iter() {
+            assert!(
+                contacts
+                    .iter()
+                    .any(|contact| contact.entities.contains(&big)
+                        && contact.entities.contains(entity)),
+                "missed the overlap between the big body and {:?}",
+                entity
+            );
+        }
+    }
+
+    // A ball hitting a box above its centre sets it spinning, without changing the total
+    // angular momentum around the origin
+    #[test]
+    fn off_centre_hits_spin_bodies() {
+        let settings = PhysicsSettings {
+            gravity: false,
+            sleeping: false,
+            continuous: false,
+            ..default()
+        };
+        let square = ColliderShape::Aabb {
+            half_extents: Vec2::splat(4.0),
+        };
+        let mut bodies = [
+            body(0, Vec2::new(-6.0, 3.0), Vec2::new(50.0, 0.0), 1.0),
+            shaped(1, Vec2::ZERO, &square),
+        ];
+        let angular_momentum = |bodies: &[Body]| -> f32 {
+            bodies
+                .iter()
+                .map(|body| {
+                    body.mass * body.position.xy().perp_dot(body.velocity.xy())
+                        + body.inertia * body.angular_velocity
+                })
+                .sum()
+        };
+        let start = angular_momentum(&bodies);
+
+        run(&mut bodies, &settings, 10);
+        assert!(
+            bodies[1].angular_velocity < 0.0,
+            "box hit above its centre should turn clockwise"
+        );
+        assert!(bodies[1].angle < 0.0);
+        let end = angular_momentum(&bodies);
+        assert!(
+            (end - start).abs() < 1e-3 * start.abs(),
+            "angular momentum changed from {} to {}",
+            start,
+            end
+        );
+    }
+    // Newtonian pull between the two bodies of an orbit
+    fn orbit_gravity(bodies: &mut [Body]) {
+        let offset = bodies[1].position - bodies[0].position;
+        let force = offset.normalize() * bodies[0].mass * bodies[1].mass / offset.length_squared();
+        bodies[0].acceleration = force / bodies[0].mass;
+        bodies[1].acceleration = -force / bodies[1].mass;
+    }
+
+    fn orbit_energy(bodies: &[Body]) -> f32 {
+        let kinetic: f32 = bodies
+            .iter()
+            .map(|body| 0.5 * body.mass * body.velocity.length_squared())
+            .sum();
+        kinetic - bodies[0].mass * bodies[1].mass / bodies[0].position.distance(bodies[1].position)
+    }
+
+    // Largest relative change of the total energy over about 50 circular orbits
+    // of 200 steps each
+    fn orbit_drift(integrator: Integrator) -> f32 {
+        let (heavy, light, radius): (f32, f32, f32) = (1000.0, 1.0, 100.0);
+        let speed = ((heavy + light) / radius).sqrt();
+        let share = light / (heavy + light);
+        let mut bodies = [
+            Body {
+                mass: heavy,
+                ..body(
+                    0,
+                    Vec2::new(-radius * share, 0.0),
+                    Vec2::new(0.0, -speed * share),
+                    1.0,
+                )
+            },
+            Body {
+                mass: light,
+                ..body(
+                    1,
+                    Vec2::new(radius * (1.0 - share), 0.0),
+                    Vec2::new(0.0, speed * (1.0 - share)),
+                    1.0,
+                )
+            },
+        ];
+
+        orbit_gravity(&mut bodies);
+        let start = orbit_energy(&bodies);
+        let mut drift: f32 = 0.0;
+        for _ in 0..10_000 {
+            integrate(&mut bodies, integrator, 1.0, &mut orbit_gravity);
+            orbit_gravity(&mut bodies);
+            drift = drift.max(((orbit_energy(&bodies) - start) / start).abs());
+        }
+        drift
+    }
+
+    #[test]
+    fn integrators_keep_orbits() {
+        let euler = orbit_drift(Integrator::SymplecticEuler);
+        let verlet = orbit_drift(Integrator::VelocityVerlet);
+        let rk4 = orbit_drift(Integrator::Rk4);
+
+        assert!(euler < 5e-3, "symplectic Euler drifted by {:.2e}", euler);
+        assert!(verlet < 1e-4, "velocity Verlet drifted by {:.2e}", verlet);
+        assert!(rk4 < 1e-4, "RK4 drifted by {:.2e}", rk4);
+        // The higher order integrators do better than the first order one
+        assert!(verlet < euler && rk4 < euler);
+    }
+
+    // Resting bodies leave the search to the awake ones around them, which still find them
+    #[test]
+    fn sleeping_bodies_are_found_by_awake_ones() {
+        let asleep = SleepState {
+            asleep: true,
+            still_for: 1.0,
+        };
+        let bodies = [
+            Body {
+                sleep: asleep,
+                ..body(0, Vec2::new(-1.9, 0.0), Vec2::ZERO, 1.0)
+            },
+            Body {
+                sleep: asleep,
+                ..body(1, Vec2::ZERO, Vec2::ZERO, 1.0)
+            },
+            body(2, Vec2::new(1.9, 0.0), Vec2::ZERO, 1.0),
+        ];
+        let bounds = bounds(100.0);
+        let mut quadtree = Quadtree::default();
+        quadtree.rebuild(
+            bounds.rect(),
+            bodies.iter().map(|body| (body.entity, body.position)),
+        );
+        let index: HashMap<Entity, usize> = bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| (body.entity, i))
+            .collect();
+
+        let pairs = find_pairs(&bodies, &quadtree, &index, 0.03, &bounds, Boundary::Walls);
+        assert_eq!(pairs, vec![[1, 2]]);
+    }
+
+    // A ball thrown into a corner touches both walls there, and each contact
+    // points away from its own wall
+    #[test]
+    fn corners_report_both_walls() {
+        let settings = PhysicsSettings {
+            gravity: false,
+            ..default()
+        };
+        let bounds = bounds(20.0);
+        let walls = edge_walls(&bounds);
+        let mut bodies = [body(0, Vec2::splat(-18.5), Vec2::splat(-50.0), 1.0)];
+
+        let mut quadtree = Quadtree::default();
+        let output = step(
+            &mut bodies,
+            &walls,
+            &[],
+            &GravityField::NONE,
+            &mut quadtree,
+            &bounds,
+            &settings,
+        );
+        let normal = |wall: &Wall| {
+            output
+                .contacts
+                .iter()
+                .find(|contact| contact.entities.contains(&wall.entity))
+                .map(|contact| contact.normal)
+        };
+        // Bottom and left walls
+        assert_eq!(output.contacts.len(), 2);
+        assert!(normal(&walls[0]).is_some_and(|normal| normal.abs_diff_eq(Vec3::Y, 1e-5)));
+        assert!(normal(&walls[3]).is_some_and(|normal| normal.abs_diff_eq(Vec3::X, 1e-5)));
+    }
 }
@@ -0,0 +1,189 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::EguiContexts;
+
+use crate::physics::{update_physics, Physics, PhysicsSettings, RigidBodyKind};
+use crate::quad_trees::{QuadTreeDetect, Quadtree};
+use crate::replay::SimMode;
+use crate::scenario::{spawn_particle, ScenarioAssets};
+
+// Spring pulling a dragged body towards the cursor, per unit of mass
+const DRAG_STIFFNESS: f32 = 200.0;
+// Critically damped, so the body doesn't swing around the cursor
+const DRAG_DAMPING: f32 = 28.0;
+// Radius of the bodies spawned with the right mouse button
+const SPAWN_RADIUS: f32 = 5.0;
+
+pub struct PickingPlugin;
+impl Plugin for PickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cursor>()
+            .init_resource::<Dragged>()
+            .add_systems(
+                Update,
+                (track_cursor, (pick_body, spawn_body, draw_drag))
+                    .chain()
+                    .run_if(in_state(SimMode::Live)),
+            )
+            .add_systems(
+                FixedUpdate,
+                pull_dragged
+                    .before(update_physics)
+                    .run_if(in_state(SimMode::Live)),
+            );
+    }
+}
+
+// Cursor in world coordinates, None while it is outside the window
+#[derive(Resource, Default)]
+pub struct Cursor {
+    pub position: Option<Vec2>,
+    // Smoothed over the last few frames, given to a body when it is let go
+    pub velocity: Vec2,
+}
+
+// Body held with the left mouse button
+#[derive(Resource, Default)]
+pub struct Dragged(pub Option<Entity>);
+
+fn track_cursor(
+    mut cursor: ResMut<Cursor>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    time: Res<Time>,
+    mut contexts: EguiContexts,
+) {
+    // Clicks on the settings panel shouldn't reach the bodies behind it
+    if contexts.ctx_mut().wants_pointer_input() {
+        cursor.position = None;
+        cursor.velocity = Vec2::ZERO;
+        return;
+    }
+    let position = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .zip(cameras.get_single().ok())
+        .and_then(|(position, (camera, transform))| {
+            camera.viewport_to_world_2d(transform, position)
+        });
+
+    let dt = time.delta_seconds();
+    if let (Some(old), Some(new)) = (cursor.position, position) {
+        if dt > 0.0 {
+            cursor.velocity = cursor.velocity.lerp((new - old) / dt, 0.3);
+        }
+    } else {
+        cursor.velocity = Vec2::ZERO;
+    }
+    cursor.position = position;
+}
+
+// Grabs the body under the cursor, and flicks it with the cursor velocity when let go
+fn pick_body(
+    buttons: Res<ButtonInput<MouseButton>>,
+    cursor: Res<Cursor>,
+    quadtree: Res<Quadtree>,
+    mut dragged: ResMut<Dragged>,
+    mut bodies: Query<(&mut Physics, Option<&RigidBodyKind>), With<QuadTreeDetect>>,
+) {
+    if buttons.just_released(MouseButton::Left) {
+        if let Some((mut physics, _)) = dragged
+            .0
+            .take()
+            .and_then(|entity| bodies.get_mut(entity).ok())
+        {
+            physics.velocity = cursor.velocity.extend(0.0);
+            physics.wake();
+        }
+        return;
+    }
+
+    let Some(position) = cursor.position else {
+        return;
+    };
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    // The quadtree only holds centres, so look as far as the biggest body reaches
+    let reach = bodies
+        .iter()
+        .map(|(physics, _)| physics.collider_radius)
+        .fold(0.0, f32::max);
+    let mut candidates = Vec::new();
+    quadtree.query(
+        Rect::from_center_half_size(position, Vec2::splat(reach)),
+        &mut candidates,
+    );
+
+    // Closest movable body the cursor is inside of
+    dragged.0 = candidates
+        .into_iter()
+        .filter_map(|entity| {
+            let (physics, kind) = bodies.get(entity).ok()?;
+            let distance = physics.position.xy().distance(position);
+            let movable = kind.copied().unwrap_or_default() == RigidBodyKind::Dynamic;
+            (movable && distance <= physics.collider_radius).then_some((entity, distance))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _)| entity);
+}
+
+// Pulls the dragged body with a spring, so it still collides on the way to the cursor
+fn pull_dragged(
+    mut dragged: ResMut<Dragged>,
+    cursor: Res<Cursor>,
+    settings: Res<PhysicsSettings>,
+    mut bodies: Query<&mut Physics>,
+) {
+    let Some(entity) = dragged.0 else {
+        return;
+    };
+    let Ok(mut physics) = bodies.get_mut(entity) else {
+        // Despawned while held
+        dragged.0 = None;
+        return;
+    };
+    let Some(target) = cursor.position else {
+        return;
+    };
+
+    let stretch = target.extend(0.0) - physics.position;
+    let pull = stretch * DRAG_STIFFNESS - physics.velocity * DRAG_DAMPING;
+    physics.velocity += pull * settings.time_step;
+    physics.wake();
+}
+
+fn spawn_body(
+    mut commands: Commands,
+    buttons: Res<ButtonInput<MouseButton>>,
+    cursor: Res<Cursor>,
+    assets: Option<Res<ScenarioAssets>>,
+) {
+    let (Some(position), Some(assets)) = (cursor.position, assets) else {
+        return;
+    };
+    if buttons.just_pressed(MouseButton::Right) {
+        let mass = SPAWN_RADIUS * SPAWN_RADIUS * std::f32::consts::PI;
+        spawn_particle(
+            &mut commands,
+            &assets.texture,
+            Physics::new(position.extend(0.0), Vec3::ZERO, mass, SPAWN_RADIUS),
+            "spawned",
+        );
+    }
+}
+
+fn draw_drag(
+    mut gizmos: Gizmos,
+    dragged: Res<Dragged>,
+    cursor: Res<Cursor>,
+    bodies: Query<&Transform>,
+) {
+    let (Some(entity), Some(target)) = (dragged.0, cursor.position) else {
+        return;
+    };
+    if let Ok(transform) = bodies.get(entity) {
+        gizmos.line_2d(transform.translation.xy(), target, Color::GREEN);
+    }
+}
@@ -7,16 +7,33 @@ pub static X_EXTENT: f32 = 600.0f32;
 pub static Y_EXTENT: f32 = 400.0f32;
 const ITEM_PER_QUAD: usize = 100;
 
+// Half size of the simulated world, centered on the origin
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct WorldBounds {
+    pub half_extents: Vec2,
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec2::new(X_EXTENT, Y_EXTENT),
+        }
+    }
+}
+
+impl WorldBounds {
+    pub fn rect(&self) -> Rect {
+        Rect::from_center_half_size(Vec2::ZERO, self.half_extents)
+    }
+}
+
 pub struct QuadtreePlugin;
 impl Plugin for QuadtreePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Quadtree::new(
-            Rect::default(),
-            ITEM_PER_QUAD,
-            Vec3::default(),
-        ))
-        .add_systems(PreUpdate, (clean_quadtree, update_quadtree_system))
-        .add_systems(Update, draw_quadtree);
+        app.init_resource::<WorldBounds>()
+            .init_resource::<Quadtree>()
+            .add_systems(PreUpdate, clean_quadtree)
+            .add_systems(Update, draw_quadtree);
     }
 }
 
@@ -38,6 +55,12 @@ pub struct Quadtree {
     center: Vec3,
 }
 
+impl Default for Quadtree {
+    fn default() -> Self {
+        Quadtree::new(Rect::default(), ITEM_PER_QUAD, Vec3::default())
+    }
+}
+
 impl Quadtree {
     fn new(bounds: Rect, capacity: usize, center: Vec3) -> Self {
         Self {
@@ -49,6 +72,14 @@ impl Quadtree {
         }
     }
 
+    // Empties the tree and fills it again, the physics step does this before every broadphase
+    pub fn rebuild(&mut self, bounds: Rect, items: impl IntoIterator<Item = (Entity, Vec3)>) {
+        *self = Quadtree::new(bounds, ITEM_PER_QUAD, bounds.center().extend(0.0));
+        for (entity, position) in items {
+            self.insert(entity, Transform::from_translation(position));
+        }
+    }
+
     // Method to insert an entity into the quadtree
     pub fn insert(&mut self, entity: Entity, transform: Transform) {
         if !self.point_in_bounds(transform.translation) {
@@ -192,6 +223,19 @@ impl Quadtree {
             }
         }
     }
+    // Area and contents of every leaf
+    pub fn leaves(&self, found: &mut Vec<(Rect, Vec<Entity>)>) {
+        if let Some(children) = &self.children {
+            for child in children.iter() {
+                child.leaves(found);
+            }
+        } else {
+            found.push((
+                self.bounds,
+                self.items.iter().map(|item| item.entity).collect(),
+            ));
+        }
+    }
     pub fn huntsman(&self, grav: &mut Vec<(f32, Vec3)>) {
         if let Some(children) = &self.children {
             for child in children {
@@ -203,22 +247,6 @@ impl Quadtree {
     }
 }
 
-// Bevy system to update the quadtree
-fn update_quadtree_system(
-    mut quadtree: ResMut<Quadtree>,
-    query: Query<(Entity, &Transform), With<QuadTreeDetect>>,
-) {
-    *quadtree = Quadtree::new(
-        Rect::new(-X_EXTENT, -Y_EXTENT, X_EXTENT, Y_EXTENT),
-        ITEM_PER_QUAD,
-        Vec3::ZERO,
-    );
-
-    for (entity, transform) in query.iter() {
-        quadtree.insert(entity, *transform)
-    }
-}
-
 #[derive(Component, Debug)]
 struct QuadTreeLine;
 
@@ -0,0 +1,500 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::collider::ColliderShape;
+use crate::physics::Physics;
+use crate::scenario::ScenarioAssets;
+
+const RECORDING_PATH: &str = "recording.simlog";
+const MAGIC: &[u8; 8] = b"SIMLOG02";
+// id, position xy, rotation, velocity xy, size xy
+const ENTITY_SIZE: usize = 4 + 4 * 7;
+// How much faster than the playback speed the arrow keys scrub
+const SCRUB_SPEED: f32 = 4.0;
+
+pub struct ReplayPlugin;
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<SimMode>()
+            .init_resource::<Recorder>()
+            .add_systems(Update, (toggle_recording, toggle_replay))
+            .add_systems(Last, record_frame.run_if(in_state(SimMode::Live)))
+            .add_systems(OnEnter(SimMode::Replay), hide_live)
+            .add_systems(OnExit(SimMode::Replay), (show_live, despawn_ghosts))
+            .add_systems(
+                Update,
+                (replay_controls, play_replay)
+                    .chain()
+                    .run_if(in_state(SimMode::Replay)),
+            );
+    }
+}
+
+// Live runs the simulation, Replay plays back a recording instead
+#[derive(States, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimMode {
+    #[default]
+    Live,
+    Replay,
+}
+
+// State of one entity in one frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntityState {
+    pub id: u32,
+    pub position: Vec2,
+    pub rotation: f32,
+    pub velocity: Vec2,
+    // Drawn size of the sprite
+    pub size: Vec2,
+}
+
+// Log layout, everything little endian:
+// MAGIC, then per frame: time f32, count u32, count * (id u32, x, y, rotation, vx, vy, w, h f32)
+// The format code is copied as is in boids_quadtrees/src/replay.rs so each
+// crate builds on its own. The tests of both copies read and write
+// fixtures/replay/two_frames.bin, which keeps them in step.
+fn write_frame(writer: &mut impl Write, time: f32, states: &[EntityState]) -> io::Result<()> {
+    writer.write_all(&time.to_le_bytes())?;
+    writer.write_all(&(states.len() as u32).to_le_bytes())?;
+    for state in states {
+        writer.write_all(&state.id.to_le_bytes())?;
+        for value in [
+            state.position.x,
+            state.position.y,
+            state.rotation,
+            state.velocity.x,
+            state.velocity.y,
+            state.size.x,
+            state.size.y,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[derive(Resource, Default)]
+pub struct Recorder {
+    writer: Option<BufWriter<File>>,
+    start: f32,
+}
+
+// A recording loaded in memory, frames are decoded when they are shown
+#[derive(Resource)]
+pub struct Replay {
+    bytes: Vec<u8>,
+    // (time, byte offset) of each frame
+    frames: Vec<(f32, usize)>,
+    pub time: f32,
+    pub speed: f32,
+    pub paused: bool,
+    shown: Option<usize>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Self> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a simulation log",
+            ));
+        }
+
+        let mut frames = Vec::new();
+        let mut offset = MAGIC.len();
+        // A frame cut short by a crash is dropped
+        while offset + 8 <= bytes.len() {
+            let count = read_u32(&bytes, offset + 4) as usize;
+            let end = offset + 8 + count * ENTITY_SIZE;
+            if end > bytes.len() {
+                break;
+            }
+            frames.push((read_f32(&bytes, offset), offset));
+            offset = end;
+        }
+
+        Ok(Self {
+            bytes,
+            frames,
+            time: 0.0,
+            speed: 1.0,
+            paused: false,
+            shown: None,
+        })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.frames.last().map_or(0.0, |(time, _)| *time)
+    }
+
+    // Last frame recorded at or before the playhead
+    pub fn frame_at(&self, time: f32) -> Option<usize> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let next = self
+            .frames
+            .partition_point(|(frame_time, _)| *frame_time <= time);
+        Some(next.saturating_sub(1))
+    }
+
+    pub fn frame(&self, index: usize) -> impl Iterator<Item = EntityState> + '_ {
+        let offset = self.frames[index].1;
+        let count = read_u32(&self.bytes, offset + 4) as usize;
+        (0..count).map(move |i| {
+            let at = offset + 8 + i * ENTITY_SIZE;
+            EntityState {
+                id: read_u32(&self.bytes, at),
+                position: Vec2::new(read_f32(&self.bytes, at + 4), read_f32(&self.bytes, at + 8)),
+                rotation: read_f32(&self.bytes, at + 12),
+                velocity: Vec2::new(
+                    read_f32(&self.bytes, at + 16),
+                    read_f32(&self.bytes, at + 20),
+                ),
+                size: Vec2::new(
+                    read_f32(&self.bytes, at + 24),
+                    read_f32(&self.bytes, at + 28),
+                ),
+            }
+        })
+    }
+}
+
+// Stand-in drawn for a recorded entity while replaying
+#[derive(Component)]
+struct Ghost(u32);
+
+fn toggle_recording(
+    mut recorder: ResMut<Recorder>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mode: Res<State<SimMode>>,
+    time: Res<Time>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyR) || *mode.get() != SimMode::Live {
+        return;
+    }
+
+    if let Some(mut writer) = recorder.writer.take() {
+        if let Err(error) = writer.flush() {
+            error!("failed to finish {RECORDING_PATH}: {error}");
+        }
+        info!("stopped recording");
+        return;
+    }
+
+    let file = File::create(RECORDING_PATH).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        writer.write_all(MAGIC)?;
+        Ok(writer)
+    });
+    match file {
+        Ok(writer) => {
+            recorder.writer = Some(writer);
+            recorder.start = time.elapsed_seconds();
+            info!("recording to {RECORDING_PATH}");
+        }
+        Err(error) => error!("failed to create {RECORDING_PATH}: {error}"),
+    }
+}
+
+fn record_frame(
+    mut recorder: ResMut<Recorder>,
+    query: Query<(Entity, &Transform, &Sprite, &Physics)>,
+    time: Res<Time>,
+) {
+    let start = recorder.start;
+    let Some(writer) = recorder.writer.as_mut() else {
+        return;
+    };
+
+    let states: Vec<EntityState> = query
+        .iter()
+        .map(|(entity, transform, sprite, physics)| EntityState {
+            id: entity.index(),
+            position: transform.translation.xy(),
+            rotation: transform.rotation.to_euler(EulerRot::XYZ).2,
+            velocity: physics.velocity.xy(),
+            // Every body is spawned with a custom size
+            size: sprite.custom_size.unwrap_or_default(),
+        })
+        .collect();
+
+    if let Err(error) = write_frame(writer, time.elapsed_seconds() - start, &states) {
+        error!("stopped recording: {error}");
+        recorder.writer = None;
+    }
+}
+
+// P switches between the live simulation and the last recording
+fn toggle_replay(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mode: Res<State<SimMode>>,
+    mut next_mode: ResMut<NextState<SimMode>>,
+    mut recorder: ResMut<Recorder>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyP) {
+        return;
+    }
+
+    match mode.get() {
+        SimMode::Live => {
+            // Make sure everything recorded so far is on disk
+            if let Some(mut writer) = recorder.writer.take() {
+                let _ = writer.flush();
+            }
+            match Replay::load(RECORDING_PATH) {
+                Ok(replay) => {
+                    commands.insert_resource(replay);
+                    next_mode.set(SimMode::Replay);
+                }
+                Err(error) => error!("failed to load {RECORDING_PATH}: {error}"),
+            }
+        }
+        SimMode::Replay => {
+            commands.remove_resource::<Replay>();
+            next_mode.set(SimMode::Live);
+        }
+    }
+}
+
+// Space pauses, left/right scrub (or step while paused), up/down change the speed
+fn replay_controls(
+    mut replay: ResMut<Replay>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        replay.paused = !replay.paused;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        replay.speed *= 2.0;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        replay.speed /= 2.0;
+    }
+
+    let direction = match (
+        keyboard_input.pressed(KeyCode::ArrowLeft),
+        keyboard_input.pressed(KeyCode::ArrowRight),
+    ) {
+        (true, false) => -1.0,
+        (false, true) => 1.0,
+        _ => 0.0,
+    };
+
+    if replay.paused {
+        // Step one frame at a time
+        let Some(current) = replay.frame_at(replay.time) else {
+            return;
+        };
+        let target = if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+            (current + 1).min(replay.frames.len() - 1)
+        } else if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+            current.saturating_sub(1)
+        } else {
+            return;
+        };
+        replay.time = replay.frames[target].0;
+    } else {
+        let scrub = if direction == 0.0 {
+            1.0
+        } else {
+            direction * SCRUB_SPEED
+        };
+        let duration = replay.duration();
+        replay.time =
+            (replay.time + time.delta_seconds() * replay.speed * scrub).clamp(0.0, duration);
+    }
+}
+
+fn play_replay(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    mut ghosts: Query<(Entity, &Ghost, &mut Transform, &mut Sprite)>,
+    assets: Res<ScenarioAssets>,
+) {
+    let Some(index) = replay.frame_at(replay.time) else {
+        return;
+    };
+    if replay.shown == Some(index) {
+        return;
+    }
+    replay.shown = Some(index);
+
+    let mut states: HashMap<u32, EntityState> =
+        replay.frame(index).map(|state| (state.id, state)).collect();
+
+    // Move the ghosts we already have, drop the ones missing from this frame
+    for (entity, ghost, mut transform, mut sprite) in ghosts.iter_mut() {
+        match states.remove(&ghost.0) {
+            Some(state) => {
+                transform.translation = state.position.extend(0.0);
+                transform.rotation = Quat::from_rotation_z(state.rotation);
+                sprite.custom_size = Some(state.size);
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+
+    for state in states.values() {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(state.size),
+                    ..default()
+                },
+                texture: assets.texture.clone(),
+                transform: Transform::from_translation(state.position.extend(0.0))
+                    .with_rotation(Quat::from_rotation_z(state.rotation)),
+                ..default()
+            },
+            Ghost(state.id),
+        ));
+    }
+}
+
+fn hide_live(mut query: Query<&mut Visibility, With<Physics>>) {
+    for mut visibility in query.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn show_live(mut query: Query<(&mut Visibility, Option<&ColliderShape>), With<Physics>>) {
+    for (mut visibility, shape) in query.iter_mut() {
+        // Polygons stay hidden, draw_colliders outlines them instead of a sprite
+        *visibility = match shape {
+            Some(ColliderShape::ConvexPolygon { .. }) => Visibility::Hidden,
+            _ => Visibility::Inherited,
+        };
+    }
+}
+
+fn despawn_ghosts(mut commands: Commands, query: Query<Entity, With<Ghost>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(id: u32, x: f32) -> EntityState {
+        EntityState {
+            id,
+            position: Vec2::new(x, -x),
+            rotation: x / 10.0,
+            velocity: Vec2::new(x * 2.0, 1.0),
+            size: Vec2::new(x, x * 3.0),
+        }
+    }
+
+    #[test]
+    fn frames_read_back_as_written() {
+        let frames = [
+            (0.0, vec![state(3, 1.0), state(8, 2.0)]),
+            (0.5, vec![]),
+            (1.0, vec![state(8, 4.0)]),
+        ];
+        let mut bytes = MAGIC.to_vec();
+        for (time, states) in frames.iter() {
+            write_frame(&mut bytes, *time, states).unwrap();
+        }
+
+        let replay = Replay::from_bytes(bytes).unwrap();
+        assert_eq!(replay.frames.len(), frames.len());
+        assert_eq!(replay.duration(), 1.0);
+        for (index, (time, states)) in frames.iter().enumerate() {
+            assert_eq!(replay.frames[index].0, *time);
+            assert_eq!(replay.frame(index).collect::<Vec<_>>(), *states);
+        }
+        assert_eq!(replay.frame_at(0.7), Some(1));
+    }
+
+    // Leaving the replay brings back the sprites of the live bodies, but not the hidden
+    // sprite under a polygon outline
+    #[test]
+    fn leaving_the_replay_keeps_polygons_hidden() {
+        let mut app = App::new();
+        app.add_systems(Update, (hide_live, show_live).chain());
+        let mut spawn = |shape: ColliderShape, visibility: Visibility| {
+            app.world
+                .spawn((
+                    Physics::new(Vec3::ZERO, Vec3::ZERO, 1.0, 1.0),
+                    shape,
+                    visibility,
+                ))
+                .id()
+        };
+        let circle = spawn(ColliderShape::Circle { radius: 1.0 }, Visibility::Inherited);
+        let polygon = spawn(
+            ColliderShape::ConvexPolygon {
+                vertices: vec![Vec2::ZERO, Vec2::X, Vec2::Y],
+            },
+            Visibility::Hidden,
+        );
+        app.update();
+
+        assert_eq!(
+            app.world.get::<Visibility>(circle),
+            Some(&Visibility::Inherited)
+        );
+        assert_eq!(
+            app.world.get::<Visibility>(polygon),
+            Some(&Visibility::Hidden)
+        );
+    }
+
+    // The same file is read by the tests of the other copy of this module
+    #[test]
+    fn fixture_reads_and_writes_back() {
+        let fixture = include_bytes!("../../fixtures/replay/two_frames.bin");
+        let frames = [
+            (0.0, vec![state(3, 1.0), state(8, 2.0)]),
+            (0.25, vec![state(8, 4.0)]),
+        ];
+
+        let replay = Replay::from_bytes(fixture.to_vec()).unwrap();
+        assert_eq!(replay.frames.len(), frames.len());
+        for (index, (time, states)) in frames.iter().enumerate() {
+            assert_eq!(replay.frames[index].0, *time);
+            assert_eq!(replay.frame(index).collect::<Vec<_>>(), *states);
+        }
+
+        let mut bytes = MAGIC.to_vec();
+        for (time, states) in frames.iter() {
+            write_frame(&mut bytes, *time, states).unwrap();
+        }
+        assert_eq!(bytes, fixture);
+    }
+
+    #[test]
+    fn cut_off_frames_are_dropped() {
+        let mut bytes = MAGIC.to_vec();
+        write_frame(&mut bytes, 0.0, &[state(1, 1.0)]).unwrap();
+        write_frame(&mut bytes, 0.1, &[state(1, 2.0)]).unwrap();
+        bytes.truncate(bytes.len() - 3);
+
+        let replay = Replay::from_bytes(bytes).unwrap();
+        assert_eq!(replay.frames.len(), 1);
+        assert!(Replay::from_bytes(b"NOTALOG!".to_vec()).is_err());
+    }
+}
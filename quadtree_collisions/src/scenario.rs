@@ -0,0 +1,768 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    ecs::system::EntityCommands,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    utils::BoxedFuture,
+};
+use rand::{rngs::ThreadRng, thread_rng, Rng};
+use serde::Deserialize;
+
+use crate::collider::ColliderShape;
+use crate::emitter::{Emitter, Sink};
+use crate::fluid::FluidSettings;
+use crate::gravity::{Attractor, GravitySettings};
+use crate::joints::{Joint, JointKind};
+use crate::material::PhysicsMaterial;
+use crate::physics::{
+    world_edges, Boundary, Integrator, Physics, PhysicsSettings, RigidBodyKind, Segment, WorldEdge,
+};
+use crate::quad_trees::{QuadTreeDetect, WorldBounds};
+use crate::sensor::Sensor;
+use crate::visualisation::Visualisation;
+
+// Another scenario can be picked with `cargo run -- scenarios/fluid.scenario.ron`
+const SCENARIO_PATH: &str = "scenarios/particles.scenario.ron";
+
+pub struct ScenarioPlugin;
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Scenario>()
+            .register_asset_loader(ScenarioLoader)
+            .add_systems(Startup, load_scenario)
+            .add_systems(Update, spawn_scenario);
+    }
+}
+
+// Everything needed to set up a run, loaded from a `.scenario.ron` file.
+// Editing the file while the app runs respawns the scene.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Scenario {
+    pub bounds: Vec2,
+    pub solver: SolverSettings,
+    pub groups: Vec<SpawnGroup>,
+    // Static walls inside the world
+    #[serde(default)]
+    pub segments: Vec<SegmentSpawn>,
+    // Particles held together by joints
+    #[serde(default)]
+    pub structures: Vec<Structure>,
+    #[serde(default)]
+    pub gravity: GravitySettings,
+    // Only pulling with Attractors gravity
+    #[serde(default)]
+    pub attractors: Vec<AttractorSpawn>,
+    // Spawning bodies as the simulation runs
+    #[serde(default)]
+    pub emitters: Vec<Emitter>,
+    // Despawning the bodies that reach them
+    #[serde(default)]
+    pub sinks: Vec<Sink>,
+    // Starting colouring, can be changed while running
+    #[serde(default)]
+    pub visualisation: Visualisation,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct SolverSettings {
+    pub iterations: usize,
+    pub time_step: f32,
+    #[serde(default = "default_substeps")]
+    pub substeps: usize,
+    pub restitution: f32,
+    #[serde(default)]
+    pub boundary: Boundary,
+    #[serde(default)]
+    pub integrator: Integrator,
+    // Simulate a liquid instead of hard spheres
+    #[serde(default)]
+    pub fluid: Option<FluidSettings>,
+}
+
+fn default_substeps() -> usize {
+    1
+}
+
+fn default_radius() -> RadiusDistribution {
+    RadiusDistribution::Fixed(1.0)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpawnGroup {
+    pub species: String,
+    pub count: usize,
+    pub region: Region,
+    pub velocity: VelocityDistribution,
+    // Ignored when a shape is given
+    #[serde(default = "default_radius")]
+    pub radius: RadiusDistribution,
+    // Circles of the sampled radius when missing
+    #[serde(default)]
+    pub shape: Option<ColliderShape>,
+    // Defaults to the area of each body
+    #[serde(default)]
+    pub mass: Option<f32>,
+    // Uses the solver restitution and no friction when missing
+    #[serde(default)]
+    pub material: Option<PhysicsMaterial>,
+    #[serde(default)]
+    pub kind: RigidBodyKind,
+    // Zones reporting what enters them instead of colliding
+    #[serde(default)]
+    pub sensor: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct SegmentSpawn {
+    pub start: Vec2,
+    pub end: Vec2,
+    #[serde(default)]
+    pub material: Option<PhysicsMaterial>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct AttractorSpawn {
+    pub position: Vec2,
+    pub attractor: Attractor,
+}
+
+// Keep the spacing above twice the radius, or neighbours keep colliding with each other
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum Structure {
+    // `links` particles in a line from `start` to `end`, hanging from `start` if pinned
+    Chain {
+        start: Vec2,
+        end: Vec2,
+        links: usize,
+        radius: f32,
+        joint: JointStyle,
+        pinned: bool,
+    },
+    // Grid of particles going right and down from `top_left`, hanging from its top row if pinned
+    Cloth {
+        top_left: Vec2,
+        columns: usize,
+        rows: usize,
+        spacing: f32,
+        radius: f32,
+        joint: JointStyle,
+        pinned: bool,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum JointStyle {
+    Rigid,
+    Spring { stiffness: f32, damping: f32 },
+    // Can get `slack` times longer than the spawn distance
+    Rope { slack: f32 },
+}
+
+impl JointStyle {
+    fn joint(self, body: Entity, other: Entity, length: f32) -> Joint {
+        let kind = match self {
+            JointStyle::Rigid => JointKind::Distance { other, length },
+            JointStyle::Spring { stiffness, damping } => JointKind::Spring {
+                other,
+                rest_length: length,
+                stiffness,
+                damping,
+            },
+            JointStyle::Rope { slack } => JointKind::Rope {
+                other,
+                max_length: length * (1.0 + slack),
+            },
+        };
+        Joint { body, kind }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum Region {
+    World,
+    Rect { min: Vec2, max: Vec2 },
+    Circle { center: Vec2, radius: f32 },
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum VelocityDistribution {
+    Fixed(Vec2),
+    // Each component picked in -max_speed..max_speed
+    Uniform {
+        max_speed: f32,
+    },
+    // Direction within `spread` radians of `direction`
+    Cone {
+        direction: Vec2,
+        spread: f32,
+        min_speed: f32,
+        max_speed: f32,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum RadiusDistribution {
+    Fixed(f32),
+    Uniform { min: f32, max: f32 },
+    // (radius, weight) pairs, a radius is picked with a chance proportional to its weight
+    Weighted(Vec<(f32, f32)>),
+}
+
+impl Region {
+    pub fn sample(&self, rgen: &mut ThreadRng, bounds: &WorldBounds) -> Vec2 {
+        match *self {
+            Region::World => Vec2::new(
+                rgen.gen_range(-bounds.half_extents.x..bounds.half_extents.x),
+                rgen.gen_range(-bounds.half_extents.y..bounds.half_extents.y),
+            ),
+            Region::Rect { min, max } => {
+                Vec2::new(rgen.gen_range(min.x..=max.x), rgen.gen_range(min.y..=max.y))
+            }
+            Region::Circle { center, radius } => {
+                // sqrt keeps the points evenly spread over the disc
+                let r = radius * rgen.gen_range(0.0f32..=1.0).sqrt();
+                center + Vec2::from_angle(rgen.gen_range(0.0..(2.0 * PI))) * r
+            }
+        }
+    }
+
+    pub fn contains(&self, point: Vec2, bounds: &WorldBounds) -> bool {
+        match *self {
+            Region::World => point.abs().cmple(bounds.half_extents).all(),
+            Region::Rect { min, max } => point.cmpge(min).all() && point.cmple(max).all(),
+            Region::Circle { center, radius } => point.distance_squared(center) <= radius * radius,
+        }
+    }
+}
+
+impl VelocityDistribution {
+    pub fn sample(&self, rgen: &mut ThreadRng) -> Vec2 {
+        match *self {
+            VelocityDistribution::Fixed(velocity) => velocity,
+            VelocityDistribution::Uniform { max_speed } => Vec2::new(
+                rgen.gen_range(-max_speed..=max_speed),
+                rgen.gen_range(-max_speed..=max_speed),
+            ),
+            VelocityDistribution::Cone {
+                direction,
+                spread,
+                min_speed,
+                max_speed,
+            } => {
+                let angle = direction.y.atan2(direction.x) + rgen.gen_range(-spread..=spread);
+                Vec2::from_angle(angle) * rgen.gen_range(min_speed..=max_speed)
+            }
+        }
+    }
+}
+
+impl RadiusDistribution {
+    pub fn sample(&self, rgen: &mut ThreadRng) -> f32 {
+        match self {
+            RadiusDistribution::Fixed(radius) => *radius,
+            RadiusDistribution::Uniform { min, max } => rgen.gen_range(*min..=*max),
+            RadiusDistribution::Weighted(choices) => {
+                let total: f32 = choices.iter().map(|(_, weight)| weight).sum();
+                let mut pick = rgen.gen_range(0.0..total.max(f32::EPSILON));
+                for (radius, weight) in choices.iter() {
+                    if pick < *weight {
+                        return *radius;
+                    }
+                    pick -= weight;
+                }
+                choices.last().map_or(1.0, |(radius, _)| *radius)
+            }
+        }
+    }
+}
+
+// A range in a scenario file that has nothing to pick from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangeError {
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl std::fmt::Display for RangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} goes from {} down to {}",
+            self.name, self.min, self.max
+        )
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+// Picking from min..=max panics when max is below min
+fn check_range(name: &'static str, min: f32, max: f32) -> Result<(), RangeError> {
+    if min <= max {
+        Ok(())
+    } else {
+        Err(RangeError { name, min, max })
+    }
+}
+
+impl Region {
+    pub fn validate(&self) -> Result<(), RangeError> {
+        match *self {
+            Region::Rect { min, max } => {
+                check_range("rect x", min.x, max.x)?;
+                check_range("rect y", min.y, max.y)
+            }
+            Region::World | Region::Circle { .. } => Ok(()),
+        }
+    }
+}
+
+impl VelocityDistribution {
+    pub fn validate(&self) -> Result<(), RangeError> {
+        match *self {
+            VelocityDistribution::Fixed(_) => Ok(()),
+            VelocityDistribution::Uniform { max_speed } => {
+                check_range("velocity", -max_speed, max_speed)
+            }
+            VelocityDistribution::Cone {
+                spread,
+                min_speed,
+                max_speed,
+                ..
+            } => {
+                check_range("cone spread", -spread, spread)?;
+                check_range("cone speed", min_speed, max_speed)
+            }
+        }
+    }
+}
+
+impl RadiusDistribution {
+    pub fn validate(&self) -> Result<(), RangeError> {
+        match *self {
+            RadiusDistribution::Uniform { min, max } => check_range("radius", min, max),
+            RadiusDistribution::Fixed(_) | RadiusDistribution::Weighted(_) => Ok(()),
+        }
+    }
+}
+
+impl Scenario {
+    // Rejects what would panic or misbehave once spawned, so a bad hot reload
+    // only logs an error and keeps the current scene
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // The world region picks from -bounds..bounds, which can't be empty
+        if !self.bounds.cmpgt(Vec2::ZERO).all() {
+            let bounds = self.bounds.min_element();
+            return Err(Box::new(RangeError {
+                name: "bounds",
+                min: -bounds,
+                max: bounds,
+            }));
+        }
+        for group in self.groups.iter() {
+            group.region.validate()?;
+            group.velocity.validate()?;
+            group.radius.validate()?;
+            if let Some(shape) = &group.shape {
+                shape.validate()?;
+            }
+        }
+        for emitter in self.emitters.iter() {
+            emitter.region.validate()?;
+            emitter.velocity.validate()?;
+            emitter.radius.validate()?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct ScenarioLoader;
+
+impl AssetLoader for ScenarioLoader {
+    type Asset = Scenario;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Scenario, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let scenario: Scenario = ron::de::from_bytes(&bytes)?;
+            scenario.validate()?;
+            Ok(scenario)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scenario.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct ScenarioAssets {
+    pub scenario: Handle<Scenario>,
+    // Texture shared by every particle
+    pub texture: Handle<Image>,
+}
+
+// Marks entities that get despawned when the scenario is reloaded
+#[derive(Component)]
+pub struct ScenarioEntity;
+
+// Name of the spawn group the body came from
+#[derive(Component, Debug, Clone)]
+pub struct Species(pub String);
+
+fn load_scenario(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    commands.insert_resource(ScenarioAssets {
+        scenario: asset_server.load(
+            std::env::args()
+                .nth(1)
+                .unwrap_or_else(|| SCENARIO_PATH.to_string()),
+        ),
+        texture: images.add(circle_texture(32)),
+    });
+}
+
+// White disc used by every particle, so all of them batch into the same draw call
+fn circle_texture(size: u32) -> Image {
+    let center = size as f32 / 2.0;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let offset = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - Vec2::splat(center);
+            // Smooth the edge over one texel
+            let alpha = (center - offset.length()).clamp(0.0, 1.0);
+            data.extend_from_slice(&[255, 255, 255, (alpha * 255.0) as u8]);
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+fn spawn_scenario(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Scenario>>,
+    assets: Res<ScenarioAssets>,
+    scenarios: Res<Assets<Scenario>>,
+    spawned: Query<Entity, With<ScenarioEntity>>,
+    mut bounds: ResMut<WorldBounds>,
+    mut settings: ResMut<PhysicsSettings>,
+) {
+    // Both the first load and every hot reload end up here
+    let changed = events.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+            *id == assets.scenario.id()
+        }
+        _ => false,
+    });
+    if !changed {
+        return;
+    }
+    let Some(scenario) = scenarios.get(&assets.scenario) else {
+        return;
+    };
+
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    bounds.half_extents = scenario.bounds;
+    settings.iterations = scenario.solver.iterations;
+    settings.time_step = scenario.solver.time_step;
+    settings.substeps = scenario.solver.substeps;
+    settings.restitution = scenario.solver.restitution;
+    settings.boundary = scenario.solver.boundary;
+    settings.integrator = scenario.solver.integrator;
+    settings.fluid = scenario.solver.fluid;
+    commands.insert_resource(scenario.gravity);
+    commands.insert_resource(scenario.visualisation.clone());
+
+    for spawn in scenario.attractors.iter() {
+        commands.spawn((
+            spawn.attractor,
+            TransformBundle::from_transform(Transform::from_translation(
+                spawn.position.extend(0.0),
+            )),
+            ScenarioEntity,
+        ));
+    }
+
+    for emitter in scenario.emitters.iter() {
+        commands.spawn((emitter.clone(), ScenarioEntity));
+    }
+    for sink in scenario.sinks.iter() {
+        commands.spawn((*sink, ScenarioEntity));
+    }
+
+    for spawn in scenario.segments.iter() {
+        let mut segment = commands.spawn((
+            Segment {
+                start: spawn.start,
+                end: spawn.end,
+            },
+            ScenarioEntity,
+        ));
+        if let Some(material) = spawn.material {
+            segment.insert(material);
+        }
+    }
+
+    if settings.boundary == Boundary::Walls {
+        for edge in world_edges(&bounds) {
+            commands.spawn((edge, WorldEdge, ScenarioEntity));
+        }
+    }
+
+    for structure in scenario.structures.iter() {
+        spawn_structure(&mut commands, &assets.texture, structure);
+    }
+
+    let mut rgen = thread_rng();
+    for group in scenario.groups.iter() {
+        for _ in 0..group.count {
+            let (radius, area) = match &group.shape {
+                Some(shape) => (shape.bounding_radius(), shape.area()),
+                None => {
+                    let radius = group.radius.sample(&mut rgen);
+                    (radius, radius * radius * PI)
+                }
+            };
+            let mass = group.mass.unwrap_or(area);
+            let position = group.region.sample(&mut rgen, &bounds);
+            let velocity = group.velocity.sample(&mut rgen);
+
+            let mut particle = spawn_particle(
+                &mut commands,
+                &assets.texture,
+                Physics::new(position.extend(0.0), velocity.extend(0.0), mass, radius),
+                &group.species,
+            );
+            particle.insert(group.kind);
+            if let Some(material) = group.material {
+                particle.insert(material);
+            }
+            if group.sensor {
+                particle.insert(Sensor::default());
+            }
+            if let Some(shape) = &group.shape {
+                insert_shape(&mut particle, shape, position);
+            }
+        }
+    }
+}
+
+// Swaps the disc sprite of a particle for one matching its shape
+fn insert_shape(particle: &mut EntityCommands, shape: &ColliderShape, position: Vec2) {
+    particle.insert(shape.clone());
+    let (half_extents, angle) = match *shape {
+        ColliderShape::Circle { .. } => return,
+        ColliderShape::Aabb { half_extents } => (half_extents, 0.0),
+        ColliderShape::Obb {
+            half_extents,
+            angle,
+        } => (half_extents, angle),
+        // Drawn as an outline by draw_colliders
+        ColliderShape::ConvexPolygon { .. } => {
+            particle.insert(Visibility::Hidden);
+            return;
+        }
+    };
+    particle.insert((
+        Sprite {
+            custom_size: Some(half_extents * 2.0),
+            ..default()
+        },
+        // Plain white rectangle
+        Handle::<Image>::default(),
+        Transform::from_translation(position.extend(0.0))
+            .with_rotation(Quat::from_rotation_z(angle)),
+    ));
+}
+
+pub fn spawn_particle<'a>(
+    commands: &'a mut Commands,
+    texture: &Handle<Image>,
+    physics: Physics,
+    species: &str,
+) -> EntityCommands<'a> {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(physics.collider_radius * 2.0)),
+                ..default()
+            },
+            texture: texture.clone(),
+            transform: Transform::from_translation(physics.position),
+            ..default()
+        },
+        physics,
+        QuadTreeDetect,
+        ScenarioEntity,
+        Species(species.to_string()),
+    ))
+}
+
+fn spawn_structure(commands: &mut Commands, texture: &Handle<Image>, structure: &Structure) {
+    // Particles at rest, in rows, the first `pinned` of them held in place
+    let (positions, columns, radius, joint, pinned) = match *structure {
+        Structure::Chain {
+            start,
+            end,
+            links,
+            radius,
+            joint,
+            pinned,
+        } => {
+            let links = links.max(2);
+            let positions: Vec<Vec2> = (0..links)
+                .map(|i| start.lerp(end, i as f32 / (links - 1) as f32))
+                .collect();
+            // Hangs from its first link
+            (positions, links, radius, joint, usize::from(pinned))
+        }
+        Structure::Cloth {
+            top_left,
+            columns,
+            rows,
+            spacing,
+            radius,
+            joint,
+            pinned,
+        } => {
+            let columns = columns.max(1);
+            let positions: Vec<Vec2> = (0..rows * columns)
+                .map(|i| {
+                    let (row, column) = (i / columns, i % columns);
+                    top_left + Vec2::new(column as f32, -(row as f32)) * spacing
+                })
+                .collect();
+            // Hangs from its top row
+            (
+                positions,
+                columns,
+                radius,
+                joint,
+                if pinned { columns } else { 0 },
+            )
+        }
+    };
+
+    let particles: Vec<Entity> = positions
+        .iter()
+        .map(|position| {
+            let physics = Physics::new(
+                position.extend(0.0),
+                Vec3::ZERO,
+                radius * radius * PI,
+                radius,
+            );
+            spawn_particle(commands, texture, physics, "structure").id()
+        })
+        .collect();
+
+    for (i, &particle) in particles.iter().enumerate() {
+        // Join to the right and lower neighbours
+        let right = (i % columns + 1 < columns).then_some(i + 1);
+        let below = Some(i + columns).filter(|&below| below < particles.len());
+        for neighbour in [right, below].into_iter().flatten() {
+            let length = positions[i].distance(positions[neighbour]);
+            commands.spawn((
+                joint.joint(particle, particles[neighbour], length),
+                ScenarioEntity,
+            ));
+        }
+
+        if i < pinned {
+            commands.spawn((
+                Joint {
+                    body: particle,
+                    kind: JointKind::Pin {
+                        anchor: positions[i],
+                    },
+                },
+                ScenarioEntity,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_group(group: &str) -> Scenario {
+        let source = format!(
+            "(
+                bounds: (100.0, 100.0),
+                solver: (iterations: 4, time_step: 0.03, restitution: 0.5),
+                groups: [(species: \"test\", count: 10, {group})],
+            )"
+        );
+        ron::from_str(&source).unwrap()
+    }
+
+    #[test]
+    fn shipped_scenarios_are_valid() {
+        for source in [
+            include_str!("../assets/scenarios/particles.scenario.ron"),
+            include_str!("../assets/scenarios/fluid.scenario.ron"),
+            include_str!("../assets/scenarios/flow.scenario.ron"),
+        ] {
+            let scenario: Scenario = ron::from_str(source).unwrap();
+            scenario.validate().unwrap();
+        }
+    }
+
+    // Ranges `gen_range` would panic on are refused before anything gets spawned
+    #[test]
+    fn inverted_ranges_are_rejected() {
+        let valid = with_group(
+            "region: Rect(min: (-5.0, -5.0), max: (5.0, 5.0)),
+            velocity: Uniform(max_speed: 0.0),
+            radius: Uniform(min: 1.0, max: 1.0)",
+        );
+        assert!(valid.validate().is_ok());
+
+        for group in [
+            "region: Rect(min: (5.0, -5.0), max: (-5.0, 5.0)), velocity: Fixed((0.0, 0.0))",
+            "region: World, velocity: Uniform(max_speed: -1.0)",
+            "region: World, velocity: Cone(direction: (1.0, 0.0), spread: -0.5, min_speed: 1.0, max_speed: 2.0)",
+            "region: World, velocity: Cone(direction: (1.0, 0.0), spread: 0.5, min_speed: 3.0, max_speed: 2.0)",
+            "region: World, velocity: Fixed((0.0, 0.0)), radius: Uniform(min: 4.0, max: 2.0)",
+        ] {
+            assert!(with_group(group).validate().is_err(), "accepted {group}");
+        }
+
+        let empty = Scenario {
+            bounds: Vec2::new(100.0, 0.0),
+            ..valid
+        };
+        assert!(empty.validate().is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::physics::update_physics;
+use crate::replay::SimMode;
+
+pub struct SensorPlugin;
+impl Plugin for SensorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SensorEntered>()
+            .add_event::<SensorExited>()
+            .init_resource::<StepOverlaps>()
+            .add_systems(
+                FixedUpdate,
+                update_sensors
+                    .after(update_physics)
+                    .run_if(in_state(SimMode::Live)),
+            );
+    }
+}
+
+// Collider that only reports what overlaps it, bodies pass through without pushing it or being pushed
+#[derive(Component, Debug, Default, Clone)]
+pub struct Sensor {
+    // Entities overlapping the sensor in the last step, sorted
+    pub overlapping: Vec<Entity>,
+}
+
+// First step an entity overlaps a sensor
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SensorEntered {
+    pub sensor: Entity,
+    pub entity: Entity,
+}
+
+// First step it doesn't anymore
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SensorExited {
+    pub sensor: Entity,
+    pub entity: Entity,
+}
+
+// [sensor, entity] pairs found by the last physics step
+#[derive(Resource, Default)]
+pub struct StepOverlaps(pub Vec<[Entity; 2]>);
+
+pub fn update_sensors(
+    mut step_overlaps: ResMut<StepOverlaps>,
+    mut sensors: Query<(Entity, &mut Sensor)>,
+    mut entered: EventWriter<SensorEntered>,
+    mut exited: EventWriter<SensorExited>,
+) {
+    let mut found: HashMap<Entity, Vec<Entity>> = HashMap::new();
+    for [sensor, entity] in step_overlaps.0.drain(..) {
+        found.entry(sensor).or_default().push(entity);
+    }
+
+    for (sensor, mut state) in sensors.iter_mut() {
+        let mut overlapping = found.remove(&sensor).unwrap_or_default();
+        overlapping.sort_unstable();
+        overlapping.dedup();
+
+        for &entity in overlapping.iter() {
+            if state.overlapping.binary_search(&entity).is_err() {
+                entered.send(SensorEntered { sensor, entity });
+            }
+        }
+        for &entity in state.overlapping.iter() {
+            if overlapping.binary_search(&entity).is_err() {
+                exited.send(SensorExited { sensor, entity });
+            }
+        }
+
+        // Only touch the component when something changed, so change detection stays useful
+        if state.overlapping != overlapping {
+            state.overlapping = overlapping;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::tests::{body, run};
+    use crate::physics::{Body, PhysicsSettings, RigidBodyKind};
+
+    // A particle flying through a static sensor keeps its velocity but shows up as overlapping it
+    // while inside, and a resting particle inside keeps being reported after falling asleep
+    #[test]
+    fn sensors_report_without_pushing() {
+        let settings = PhysicsSettings {
+            gravity: false,
+            ..default()
+        };
+        let mut bodies = [
+            Body {
+                kind: RigidBodyKind::Static,
+                sensor: true,
+                ..body(0, Vec2::ZERO, Vec2::ZERO, 10.0)
+            },
+            body(1, Vec2::new(-20.0, 0.0), Vec2::new(100.0, 0.0), 1.0),
+            body(2, Vec2::new(0.0, 5.0), Vec2::ZERO, 1.0),
+        ];
+        let (sensor, flying, resting) = (bodies[0].entity, bodies[1].entity, bodies[2].entity);
+
+        let mut inside_steps = 0;
+        for _ in 0..30 {
+            let output = run(&mut bodies, &settings, 1);
+            assert!(output.contacts.is_empty(), "sensor resolved a contact");
+            assert!(output.overlaps.contains(&[sensor, resting]));
+            if output.overlaps.contains(&[sensor, flying]) {
+                inside_steps += 1;
+            }
+        }
+        assert_eq!(bodies[1].velocity, Vec3::new(100.0, 0.0, 0.0));
+        assert!(inside_steps > 0, "the flying particle was never reported");
+        assert!(bodies[2].sleep.asleep);
+    }
+
+    // Switching collisions off lets bodies pass through each other, sensors still see them
+    #[test]
+    fn sensors_report_with_collisions_off() {
+        let settings = PhysicsSettings {
+            gravity: false,
+            collisions: false,
+            ..default()
+        };
+        let mut bodies = [
+            Body {
+                kind: RigidBodyKind::Static,
+                sensor: true,
+                ..body(0, Vec2::ZERO, Vec2::ZERO, 10.0)
+            },
+            body(1, Vec2::new(0.0, 5.0), Vec2::ZERO, 1.0),
+        ];
+
+        let output = run(&mut bodies, &settings, 1);
+        assert_eq!(output.overlaps, vec![[bodies[0].entity, bodies[1].entity]]);
+        assert!(output.contacts.is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+use bevy::{prelude::*, utils::HashMap};
+use std::collections::HashSet;
+
+use crate::contacts::Contact;
+use crate::physics::{Body, PhysicsSettings, RigidBodyKind, Wall};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SleepState {
+    pub asleep: bool,
+    // How long the body has been slower than the sleep speed
+    pub still_for: f32,
+}
+
+impl SleepState {
+    pub fn wake(&mut self) {
+        self.asleep = false;
+        self.still_for = 0.0;
+    }
+}
+
+// Contacts of sleeping bodies as they were when the bodies fell asleep. The broadphase
+// skips them, so they get reported from here until one of the bodies wakes up.
+#[derive(Resource, Debug, Default)]
+pub struct RestingContacts(pub HashMap<[Entity; 2], Contact>);
+
+impl RestingContacts {
+    // Forgets the contacts of bodies that woke up or are gone, keeps the ones of bodies that
+    // just fell asleep and adds all of them to the `contacts` found by the step
+    pub fn update(&mut self, bodies: &[Body], walls: &[Wall], contacts: &mut Vec<Contact>) {
+        let bodies: HashMap<Entity, &Body> =
+            bodies.iter().map(|body| (body.entity, body)).collect();
+        // Both sides can't move and at least one of them is asleep
+        let resting = |contact: &Contact| {
+            let still = contact
+                .entities
+                .iter()
+                .all(|entity| match bodies.get(entity) {
+                    Some(body) => !body.is_dynamic(),
+                    None => walls.iter().any(|wall| wall.entity == *entity),
+                });
+            let asleep = contact
+                .entities
+                .iter()
+                .any(|entity| bodies.get(entity).is_some_and(|body| body.sleep.asleep));
+            still && asleep
+        };
+
+        self.0.retain(|_, contact| resting(contact));
+        // Those of bodies that fell asleep during this step
+        for contact in contacts.iter().filter(|contact| resting(contact)) {
+            let contact = Contact {
+                impulse: 0.0,
+                ..*contact
+            };
+            self.0.insert(contact.key(), contact);
+        }
+        let reported: HashSet<[Entity; 2]> = contacts.iter().map(Contact::key).collect();
+        contacts.extend(
+            self.0
+                .iter()
+                .filter(|(key, _)| !reported.contains(*key))
+                .map(|(_, contact)| *contact),
+        );
+    }
+}
+
+// Disjoint set over the indices of the bodies
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
+
+// Wakes sleeping bodies that something awake can reach during this substep
+pub fn wake_touched(bodies: &mut [Body], pairs: &[[usize; 2]]) {
+    for &[i, j] in pairs {
+        for (sleeper, other) in [(i, j), (j, i)] {
+            if bodies[sleeper].sleep.asleep
+                && !bodies[other].sleep.asleep
+                && bodies[other].kind != RigidBodyKind::Static
+            {
+                bodies[sleeper].sleep.wake();
+            }
+        }
+    }
+}
+
+// Wakes sleeping bodies spun hard enough by a torque to get moving before they could fall
+// asleep again. Gravity doesn't count, it keeps pulling on everything resting under it.
+pub fn wake_pushed(bodies: &mut [Body], settings: &PhysicsSettings) {
+    for body in bodies.iter_mut().filter(|body| body.sleep.asleep) {
+        if body.inertia <= 0.0 {
+            continue;
+        }
+        let rim_speed = body.torque.abs() / body.inertia * settings.time_to_sleep * body.radius;
+        if rim_speed > settings.sleep_speed {
+            body.sleep.wake();
+        }
+    }
+}
+
+// Puts islands of touching or jointed bodies to sleep once all of them have been slow for long enough
+pub fn update_sleep(
+    bodies: &mut [Body],
+    // Position and angle of each body at the start of the step
+    start: &[(Vec3, f32)],
+    contacts: &HashMap<[Entity; 2], Contact>,
+    joined: &[[usize; 2]],
+    index: &HashMap<Entity, usize>,
+    settings: &PhysicsSettings,
+) {
+    for (body, (position, angle)) in bodies.iter_mut().zip(start) {
+        if !body.is_dynamic() {
+            continue;
+        }
+        // Measured over the whole step, a body resting on something jitters back and forth
+        // within it without getting anywhere. Spinning counts as moving at the speed of the rim.
+        let speed = (body.position.distance(*position) + (body.angle - angle).abs() * body.radius)
+            / settings.time_step;
+        if speed < settings.sleep_speed {
+            body.sleep.still_for += settings.time_step;
+        } else {
+            body.sleep.still_for = 0.0;
+        }
+    }
+
+    // Static and kinematic bodies don't join islands, a floor would link everything on it
+    let mut islands = UnionFind::new(bodies.len());
+    for contact in contacts.values() {
+        let [a, b] = contact.entities;
+        let (Some(&a), Some(&b)) = (index.get(&a), index.get(&b)) else {
+            continue;
+        };
+        if bodies[a].is_dynamic() && bodies[b].is_dynamic() {
+            islands.union(a, b);
+        }
+    }
+    for &[a, b] in joined {
+        if bodies[a].is_dynamic() && bodies[b].is_dynamic() {
+            islands.union(a, b);
+        }
+    }
+
+    let mut ready: HashMap<usize, bool> = HashMap::new();
+    for (i, body) in bodies.iter().enumerate() {
+        if body.is_dynamic() {
+            let still = body.sleep.still_for >= settings.time_to_sleep;
+            *ready.entry(islands.find(i)).or_insert(true) &= still;
+        }
+    }
+
+    for (i, body) in bodies.iter_mut().enumerate() {
+        if body.is_dynamic() && ready[&islands.find(i)] {
+            body.sleep.asleep = true;
+            body.velocity = Vec3::ZERO;
+            body.angular_velocity = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gravity::{GravityField, GravitySettings};
+    use crate::physics::step;
+    use crate::physics::tests::{body, bounds, edge_walls};
+    use crate::quad_trees::Quadtree;
+
+    // A column of particles dropped on the floor settles and falls asleep
+    #[test]
+    fn resting_stack_falls_asleep_under_gravity() {
+        let settings = PhysicsSettings::default();
+        let gravity = GravityField {
+            settings: GravitySettings::Uniform(Vec2::new(0.0, -100.0)),
+            attractors: &[],
+        };
+        let bounds = bounds(20.0);
+        let walls = edge_walls(&bounds);
+        let mut bodies: Vec<Body> = (0..3)
+            .map(|i| body(i, Vec2::new(0.0, -18.5 + 2.2 * i as f32), Vec2::ZERO, 1.0))
+            .collect();
+
+        let mut quadtree = Quadtree::default();
+        let mut cache = RestingContacts::default();
+        // One step the way update_physics runs it, returning its contacts
+        let mut run_step = |bodies: &mut [Body]| {
+            let mut contacts = step(
+                bodies,
+                &walls,
+                &[],
+                &gravity,
+                &mut quadtree,
+                &bounds,
+                &settings,
+            )
+            .contacts;
+            cache.update(bodies, &walls, &mut contacts);
+            contacts
+        };
+        for _ in 0..100 {
+            run_step(&mut bodies);
+        }
+        assert!(bodies.iter().all(|body| body.sleep.asleep));
+
+        // and stays put while gravity keeps pulling on it, still touching the floor and itself
+        let resting: Vec<Vec3> = bodies.iter().map(|body| body.position).collect();
+        let entities: Vec<Entity> = bodies.iter().map(|body| body.entity).collect();
+        let expected = [
+            [entities[0], entities[1]],
+            [entities[0], walls[0].entity],
+            [entities[1], entities[2]],
+        ];
+        for _ in 0..50 {
+            let mut touching: Vec<[Entity; 2]> =
+                run_step(&mut bodies).iter().map(Contact::key).collect();
+            touching.sort();
+            assert_eq!(touching, expected);
+        }
+        for (body, position) in bodies.iter().zip(resting) {
+            assert!(body.sleep.asleep);
+            assert_eq!(body.position, position);
+        }
+    }
+}
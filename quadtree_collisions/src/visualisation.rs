@@ -0,0 +1,289 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use crate::contacts::ActiveContacts;
+use crate::fluid::poly6;
+use crate::physics::{Physics, PhysicsSettings};
+use crate::quad_trees::Quadtree;
+
+// Smoothing radius for the Density mode when the scenario isn't a liquid
+const DENSITY_RADIUS: f32 = 5.0;
+
+pub struct VisualisationPlugin;
+impl Plugin for VisualisationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Visualisation>().add_systems(
+            Update,
+            (toggle_visualisation, (colour_particles, draw_heatmap)).chain(),
+        );
+    }
+}
+
+// How particles are coloured. V cycles through the modes and H toggles the heatmap.
+#[derive(Resource, Deserialize, Debug, Clone, PartialEq)]
+pub struct Visualisation {
+    #[serde(default)]
+    pub mode: ColourMode,
+    #[serde(default)]
+    pub ramp: ColourRamp,
+    // Values at or above this get the last colour, picked from the current values when missing
+    #[serde(default)]
+    pub max: Option<f32>,
+    // Colours the quadtree leaves behind the particles
+    #[serde(default)]
+    pub heatmap: bool,
+}
+
+impl Default for Visualisation {
+    fn default() -> Self {
+        Self {
+            mode: ColourMode::Plain,
+            ramp: ColourRamp::default(),
+            max: None,
+            heatmap: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColourMode {
+    #[default]
+    Plain,
+    Speed,
+    KineticEnergy,
+    // Number of bodies touching it
+    Contacts,
+    // Mass per unit of area around it
+    Density,
+}
+
+impl ColourMode {
+    fn next(self) -> Self {
+        match self {
+            ColourMode::Plain => ColourMode::Speed,
+            ColourMode::Speed => ColourMode::KineticEnergy,
+            ColourMode::KineticEnergy => ColourMode::Contacts,
+            ColourMode::Contacts => ColourMode::Density,
+            ColourMode::Density => ColourMode::Plain,
+        }
+    }
+}
+
+// RGB colours spread evenly from a value of 0 to the maximum
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ColourRamp(pub Vec<Vec3>);
+
+impl Default for ColourRamp {
+    fn default() -> Self {
+        ColourRamp(vec![
+            Vec3::new(0.1, 0.2, 0.9),
+            Vec3::new(0.1, 0.8, 0.9),
+            Vec3::new(0.2, 0.9, 0.2),
+            Vec3::new(1.0, 0.9, 0.1),
+            Vec3::new(0.9, 0.1, 0.1),
+        ])
+    }
+}
+
+impl ColourRamp {
+    // Colour at `t` between 0 and 1
+    pub fn sample(&self, t: f32) -> Color {
+        let Some(&last) = self.0.last() else {
+            return Color::WHITE;
+        };
+        let scaled = t.clamp(0.0, 1.0) * (self.0.len() - 1) as f32;
+        let i = scaled.floor() as usize;
+        let rgb = if i + 1 < self.0.len() {
+            self.0[i].lerp(self.0[i + 1], scaled - i as f32)
+        } else {
+            last
+        };
+        Color::rgb(rgb.x, rgb.y, rgb.z)
+    }
+}
+
+fn toggle_visualisation(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut visualisation: ResMut<Visualisation>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        visualisation.mode = visualisation.mode.next();
+        info!("Colouring particles by {:?}", visualisation.mode);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyH) {
+        visualisation.heatmap = !visualisation.heatmap;
+    }
+}
+
+// Value of the mode for every body
+fn measure(
+    mode: ColourMode,
+    bodies: &Query<(Entity, &Physics, &mut Sprite)>,
+    contacts: &ActiveContacts,
+    quadtree: &Quadtree,
+    settings: &PhysicsSettings,
+) -> HashMap<Entity, f32> {
+    match mode {
+        ColourMode::Plain => HashMap::new(),
+        ColourMode::Speed => bodies
+            .iter()
+            .map(|(entity, physics, _)| (entity, physics.velocity.length()))
+            .collect(),
+        ColourMode::KineticEnergy => bodies
+            .iter()
+            .map(|(entity, physics, _)| {
+                let energy = 0.5 * physics.mass * physics.velocity.length_squared();
+                (entity, energy)
+            })
+            .collect(),
+        ColourMode::Contacts => {
+            let mut counts: HashMap<Entity, f32> =
+                bodies.iter().map(|(entity, _, _)| (entity, 0.0)).collect();
+            for [a, b] in contacts.0.keys() {
+                for entity in [a, b] {
+                    if let Some(count) = counts.get_mut(entity) {
+                        *count += 1.0;
+                    }
+                }
+            }
+            counts
+        }
+        ColourMode::Density => {
+            let h = settings
+                .fluid
+                .map_or(DENSITY_RADIUS, |fluid| fluid.smoothing_radius);
+            let mut found = Vec::new();
+            bodies
+                .iter()
+                .map(|(entity, physics, _)| {
+                    let position = physics.position.xy();
+                    found.clear();
+                    quadtree.query(
+                        Rect::from_center_half_size(position, Vec2::splat(h)),
+                        &mut found,
+                    );
+                    let density = found
+                        .iter()
+                        .filter_map(|&other| bodies.get(other).ok())
+                        .map(|(_, other, _)| {
+                            let distance_squared = other.position.xy().distance_squared(position);
+                            other.mass * poly6(distance_squared, h)
+                        })
+                        .sum();
+                    (entity, density)
+                })
+                .collect()
+        }
+    }
+}
+
+// Value mapped to the last colour of the ramp. Skips the top few percent so a single
+// fast particle doesn't leave everything else at the bottom of the ramp.
+fn ramp_max(visualisation: &Visualisation, values: impl Iterator<Item = f32>) -> f32 {
+    if let Some(max) = visualisation.max {
+        return max;
+    }
+    let mut values: Vec<f32> = values.collect();
+    if values.is_empty() {
+        return 1.0;
+    }
+    let index = (values.len() - 1) * 95 / 100;
+    let (_, max, _) = values.select_nth_unstable_by(index, f32::total_cmp);
+    max.max(f32::EPSILON)
+}
+
+fn colour_particles(
+    visualisation: Res<Visualisation>,
+    mut bodies: Query<(Entity, &Physics, &mut Sprite)>,
+    contacts: Res<ActiveContacts>,
+    quadtree: Res<Quadtree>,
+    settings: Res<PhysicsSettings>,
+) {
+    if visualisation.mode == ColourMode::Plain {
+        // Only once when switching back, so other systems can still tint sprites
+        if visualisation.is_changed() {
+            for (_, _, mut sprite) in bodies.iter_mut() {
+                sprite.color = Color::WHITE;
+            }
+        }
+        return;
+    }
+
+    let values = measure(visualisation.mode, &bodies, &contacts, &quadtree, &settings);
+    let max = ramp_max(&visualisation, values.values().copied());
+    for (entity, _, mut sprite) in bodies.iter_mut() {
+        if let Some(value) = values.get(&entity) {
+            sprite.color = visualisation.ramp.sample(value / max);
+        }
+    }
+}
+
+// Reused every frame, spawning more when the tree has more leaves
+#[derive(Component)]
+struct HeatmapCell;
+
+fn draw_heatmap(
+    mut commands: Commands,
+    visualisation: Res<Visualisation>,
+    quadtree: Res<Quadtree>,
+    bodies: Query<&Physics>,
+    mut cells: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<HeatmapCell>>,
+) {
+    let mut leaves = Vec::new();
+    if visualisation.heatmap {
+        quadtree.leaves(&mut leaves);
+    }
+    // Mass per unit of area of each leaf, or the average speed or energy of what it holds
+    let values: Vec<(Rect, f32)> = leaves
+        .into_iter()
+        .filter(|(_, entities)| !entities.is_empty())
+        .map(|(area, entities)| {
+            let physics = entities
+                .iter()
+                .filter_map(|&entity| bodies.get(entity).ok());
+            let value = match visualisation.mode {
+                ColourMode::Speed => {
+                    physics.map(|p| p.velocity.length()).sum::<f32>() / entities.len() as f32
+                }
+                ColourMode::KineticEnergy => {
+                    physics
+                        .map(|p| 0.5 * p.mass * p.velocity.length_squared())
+                        .sum::<f32>()
+                        / entities.len() as f32
+                }
+                _ => physics.map(|p| p.mass).sum::<f32>() / area.size().x / area.size().y,
+            };
+            (area, value)
+        })
+        .collect();
+    let max = ramp_max(&visualisation, values.iter().map(|(_, value)| *value));
+
+    let mut cells = cells.iter_mut();
+    for (area, value) in values.iter() {
+        let mut color = visualisation.ramp.sample(value / max);
+        color.set_a(0.4);
+        let transform = Transform::from_translation(area.center().extend(-1.0));
+        if let Some((mut sprite, mut cell_transform, mut visibility)) = cells.next() {
+            sprite.color = color;
+            sprite.custom_size = Some(area.size());
+            *cell_transform = transform;
+            *visibility = Visibility::Visible;
+        } else {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(area.size()),
+                        ..default()
+                    },
+                    transform,
+                    ..default()
+                },
+                HeatmapCell,
+            ));
+        }
+    }
+    for (_, _, mut visibility) in cells {
+        *visibility = Visibility::Hidden;
+    }
+}